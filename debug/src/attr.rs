@@ -0,0 +1,340 @@
+// Collects every error found in the input so they can be reported together.
+#[derive(Default)]
+pub struct Errors(Option<syn::Error>);
+
+impl Errors {
+    pub fn push(&mut self, err: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(err),
+            None => self.0 = Some(err),
+        }
+    }
+
+    // Keeps the value of `result`, or records its error and falls back to the default.
+    pub fn take<T: Default>(&mut self, result: Result<T, syn::Error>) -> T {
+        result.unwrap_or_else(|err| {
+            self.push(err);
+            T::default()
+        })
+    }
+
+    pub fn finish(self) -> Result<(), syn::Error> {
+        match self.0 {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+// Options given through `#[debug(...)]` on the struct or enum itself.
+#[derive(Default)]
+pub struct ContainerAttrs {
+    // `#[debug(bound = "...")]`, where predicates replacing the inferred ones
+    pub bound: Option<Vec<syn::WherePredicate>>,
+    // `#[debug(phantom(...))]`, wrappers whose parameters don't need to be Debug
+    pub phantom: Vec<syn::Ident>,
+    // `#[debug(non_exhaustive)]`, end the fields with `..`
+    pub non_exhaustive: bool,
+    // `#[debug(rename_all = "...")]`, the case field names are printed in
+    pub rename_all: Option<RenameRule>,
+    // `#[debug(fields_fn)]`, also generate `debug_fields` listing the printed fields
+    pub fields_fn: bool,
+    // `#[debug(with = "path")]`, a `fn(&Self, &mut Formatter) -> fmt::Result` writing the whole value
+    pub with: Option<syn::ExprPath>,
+    // `#[debug(skip_defaults)]`, leave out fields holding their type's default value. Only the
+    // types whose default is recognised are looked at: `Option`, the std collections, `String`,
+    // `&str` and slices when empty, `bool` and the number types. Any other field is always printed,
+    // it can be given `skip_if` instead
+    pub skip_defaults: bool,
+}
+
+// The cases `rename_all` converts snake_case field names to.
+#[derive(Clone, Copy)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn from_lit(lit: &syn::Lit) -> Result<Self, syn::Error> {
+        let s = lit_str(lit)?;
+        let rule = match s.value().as_str() {
+            "lowercase" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            "PascalCase" => RenameRule::Pascal,
+            "camelCase" => RenameRule::Camel,
+            "snake_case" => RenameRule::Snake,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "kebab-case" => RenameRule::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebab,
+            _ => return Err(syn::Error::new_spanned(s, "unknown case, expected one of `lowercase`, `UPPERCASE`, `PascalCase`, `camelCase`, `snake_case`, `SCREAMING_SNAKE_CASE`, `kebab-case`, `SCREAMING-KEBAB-CASE`")),
+        };
+        Ok(rule)
+    }
+
+    pub fn apply(self, field: &str) -> String {
+        match self {
+            RenameRule::Lower => field.to_lowercase(),
+            RenameRule::Upper => field.to_uppercase(),
+            RenameRule::Pascal | RenameRule::Camel => {
+                let mut result = String::new();
+                for (i, word) in field.split('_').filter(|w| !w.is_empty()).enumerate() {
+                    let mut chars = word.chars();
+                    if let Some(first) = chars.next() {
+                        if i == 0 && matches!(self, RenameRule::Camel) {
+                            result.extend(first.to_lowercase());
+                        } else {
+                            result.extend(first.to_uppercase());
+                        }
+                        result.push_str(chars.as_str());
+                    }
+                }
+                result
+            }
+            RenameRule::Snake => field.to_owned(),
+            RenameRule::ScreamingSnake => field.to_uppercase(),
+            RenameRule::Kebab => field.replace('_', "-"),
+            RenameRule::ScreamingKebab => field.to_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+pub fn parse_container_attrs(attrs: &[syn::Attribute]) -> Result<ContainerAttrs, syn::Error> {
+    let mut result = ContainerAttrs::default();
+    let mut errors = Errors::default();
+    for item in debug_attr_items(attrs, &mut errors) {
+        if let Err(err) = parse_container_item(&item, &mut result) {
+            errors.push(err);
+        }
+    }
+    errors.finish()?;
+    Ok(result)
+}
+
+fn parse_container_item(item: &syn::Meta, result: &mut ContainerAttrs) -> Result<(), syn::Error> {
+    match item {
+        syn::Meta::NameValue(nv) if nv.path.is_ident("bound") => {
+            result.bound = Some(parse_bound(&nv.lit)?);
+        }
+        syn::Meta::List(l) if l.path.is_ident("phantom") => {
+            let mut errors = Errors::default();
+            for nested in l.nested.iter() {
+                match nested {
+                    syn::NestedMeta::Meta(syn::Meta::Path(p)) if p.get_ident().is_some() => {
+                        result.phantom.push(p.get_ident().unwrap().clone());
+                    }
+                    _ => errors.push(syn::Error::new_spanned(nested, "expected a type name")),
+                }
+            }
+            errors.finish()?;
+        }
+        syn::Meta::Path(p) if p.is_ident("fields_fn") => {
+            result.fields_fn = true;
+        }
+        syn::Meta::Path(p) if p.is_ident("non_exhaustive") => {
+            result.non_exhaustive = true;
+        }
+        syn::Meta::Path(p) if p.is_ident("skip_defaults") => {
+            result.skip_defaults = true;
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("rename_all") => {
+            result.rename_all = Some(RenameRule::from_lit(&nv.lit)?);
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("with") => {
+            result.with = Some(lit_str(&nv.lit)?.parse()?);
+        }
+        _ => return Err(syn::Error::new_spanned(item, "expected one of `bound = \"...\"`, `phantom(...)`, `non_exhaustive`, `rename_all = \"...\"`, `fields_fn`, `with = \"...\"`, `skip_defaults`")),
+    }
+    Ok(())
+}
+
+// returns true for `#[repr(packed)]` and `#[repr(packed(N))]`, whose fields can't be borrowed
+pub fn is_packed(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().filter(|attr| attr.path.is_ident("repr")).any(|attr| match attr.parse_meta() {
+        Ok(syn::Meta::List(l)) => l.nested.iter().any(|nested| match nested {
+            syn::NestedMeta::Meta(meta) => meta.path().is_ident("packed"),
+            syn::NestedMeta::Lit(_) => false,
+        }),
+        _ => false,
+    })
+}
+
+// Parses the where predicates of a `bound = "..."` option, an empty string gives no predicates.
+fn parse_bound(lit: &syn::Lit) -> Result<Vec<syn::WherePredicate>, syn::Error> {
+    let parser = syn::punctuated::Punctuated::<syn::WherePredicate, syn::Token![,]>::parse_terminated;
+    Ok(lit_str(lit)?.parse_with(parser)?.into_iter().collect())
+}
+
+// Options given through `#[debug ...]` on an enum variant.
+#[derive(Default)]
+pub struct VariantAttrs {
+    // `#[debug = "..."]`, written with the variant's fields as arguments
+    pub format: Option<syn::LitStr>,
+    // `#[debug(skip)]`, print the variant name and elide its fields
+    pub skip: bool,
+}
+
+pub fn parse_variant_attrs(attrs: &[syn::Attribute]) -> Result<VariantAttrs, syn::Error> {
+    let mut result = VariantAttrs::default();
+    let mut errors = Errors::default();
+    for item in debug_attr_items(attrs, &mut errors) {
+        match &item {
+            syn::Meta::NameValue(nv) if nv.path.is_ident("debug") => match lit_str(&nv.lit) {
+                Ok(s) => result.format = Some(s),
+                Err(err) => errors.push(err),
+            },
+            syn::Meta::Path(p) if p.is_ident("skip") => {
+                result.skip = true;
+            }
+            _ => errors.push(syn::Error::new_spanned(&item, "expected one of `skip`, `#[debug = \"...\"]`")),
+        }
+    }
+    errors.finish()?;
+    Ok(result)
+}
+
+// Options given through `#[debug ...]` on a field.
+#[derive(Default)]
+pub struct FieldAttrs {
+    // `#[debug = "..."]` or `#[debug(fmt = "...")]`, the format the field value is written with
+    pub format: Option<syn::LitStr>,
+    // `#[debug(alt = "...")]`, the format used instead when printing with `{:#?}`
+    pub alt: Option<syn::LitStr>,
+    // `#[debug(skip)]`, leave the field out entirely
+    pub skip: bool,
+    // `#[debug(skip_if = "path")]`, a `fn(&T) -> bool` telling when to leave the field out
+    pub skip_if: Option<syn::ExprPath>,
+    // `#[debug(redact)]` or `#[debug(redact(len))]`, hide the value
+    pub redact: Option<Redact>,
+    // `#[debug(rename = "...")]`, the label printed instead of the field name
+    pub rename: Option<syn::LitStr>,
+    // `#[debug(with = "path")]`, a `fn(&T, &mut Formatter) -> fmt::Result` writing the value
+    pub with: Option<syn::ExprPath>,
+    // `#[debug(bound = "...")]`, where predicates used instead of the ones inferred from the field type
+    pub bound: Option<Vec<syn::WherePredicate>>,
+    // `#[debug(limit = N)]`, print at most N items of a collection, or of a collection a field
+    // borrows. `HashMap`, `BTreeMap` and `IndexMap` print as maps, anything else as a list
+    pub limit: Option<syn::LitInt>,
+    // `#[debug(no_bound)]`, infer no bounds from the field type
+    pub no_bound: bool,
+}
+
+pub enum Redact {
+    // prints `<redacted>`
+    Hidden,
+    // prints `<redacted len=N>` using the value's `len()`
+    Len,
+}
+
+pub fn parse_field_attrs(field: &syn::Field) -> Result<FieldAttrs, syn::Error> {
+    let mut result = FieldAttrs::default();
+    let mut errors = Errors::default();
+    for item in debug_attr_items(&field.attrs, &mut errors) {
+        if let Err(err) = parse_field_item(field, &item, &mut result) {
+            errors.push(err);
+        }
+    }
+    if let Some(with) = &result.with {
+        if result.format.is_some() || result.alt.is_some() {
+            errors.push(syn::Error::new_spanned(with, "`with` cannot be combined with a format string"));
+        }
+    }
+    if let Some(limit) = &result.limit {
+        if result.with.is_some() || result.format.is_some() || result.alt.is_some() {
+            errors.push(syn::Error::new_spanned(limit, "`limit` cannot be combined with `with` or a format string"));
+        }
+    }
+    errors.finish()?;
+    Ok(result)
+}
+
+fn parse_field_item(field: &syn::Field, item: &syn::Meta, result: &mut FieldAttrs) -> Result<(), syn::Error> {
+    match item {
+        syn::Meta::NameValue(nv) if nv.path.is_ident("debug") || nv.path.is_ident("fmt") => {
+            result.format = Some(lit_str(&nv.lit)?);
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("alt") => {
+            result.alt = Some(lit_str(&nv.lit)?);
+        }
+        syn::Meta::Path(p) if p.is_ident("skip") => {
+            result.skip = true;
+        }
+        syn::Meta::Path(p) if p.is_ident("no_bound") => {
+            result.no_bound = true;
+        }
+        syn::Meta::Path(p) if p.is_ident("redact") => {
+            result.redact = Some(Redact::Hidden);
+        }
+        syn::Meta::List(l) if l.path.is_ident("redact") => {
+            match l.nested.first() {
+                Some(syn::NestedMeta::Meta(syn::Meta::Path(p))) if l.nested.len() == 1 && p.is_ident("len") => {
+                    result.redact = Some(Redact::Len);
+                }
+                _ => return Err(syn::Error::new_spanned(l, "expected `redact(len)`")),
+            }
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("rename") => {
+            let s = lit_str(&nv.lit)?;
+            if field.ident.is_none() {
+                return Err(syn::Error::new_spanned(nv, "`rename` is only supported on named fields"));
+            }
+            result.rename = Some(s);
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("limit") => {
+            match &nv.lit {
+                syn::Lit::Int(n) => {
+                    n.base10_parse::<usize>()?;
+                    result.limit = Some(n.clone());
+                }
+                lit => return Err(syn::Error::new_spanned(lit, "expected an integer literal")),
+            }
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("with") => {
+            result.with = Some(lit_str(&nv.lit)?.parse()?);
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("skip_if") => {
+            result.skip_if = Some(lit_str(&nv.lit)?.parse()?);
+        }
+        syn::Meta::NameValue(nv) if nv.path.is_ident("bound") => {
+            result.bound = Some(parse_bound(&nv.lit)?);
+        }
+        _ => return Err(syn::Error::new_spanned(item, "expected one of `skip`, `skip_if = \"...\"`, `no_bound`, `redact`, `rename = \"...\"`, `fmt = \"...\"`, `alt = \"...\"`, `with = \"...\"`, `bound = \"...\"`, `limit = N`")),
+    }
+    Ok(())
+}
+
+// Reads every `#[debug ...]` attribute and returns its options one by one: the `debug = "..."`
+// form as it is and each item of the `debug(...)` form. Other attributes, like doc comments,
+// are left alone.
+fn debug_attr_items(attrs: &[syn::Attribute], errors: &mut Errors) -> Vec<syn::Meta> {
+    let mut items = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("debug")) {
+        match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => {
+                for nested in list.nested {
+                    match nested {
+                        syn::NestedMeta::Meta(meta) => items.push(meta),
+                        syn::NestedMeta::Lit(lit) => errors.push(syn::Error::new(lit.span(), "expected an option name, not a literal")),
+                    }
+                }
+            }
+            Ok(meta @ syn::Meta::NameValue(_)) => items.push(meta),
+            Ok(meta @ syn::Meta::Path(_)) => errors.push(syn::Error::new_spanned(meta, "expected `debug = \"...\"` or `debug(...)`")),
+            Err(err) => errors.push(err),
+        }
+    }
+    items
+}
+
+fn lit_str(lit: &syn::Lit) -> Result<syn::LitStr, syn::Error> {
+    match lit {
+        syn::Lit::Str(s) => Ok(s.clone()),
+        lit => Err(syn::Error::new_spanned(lit, "expected a string literal")),
+    }
+}
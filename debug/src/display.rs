@@ -0,0 +1,102 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{self, DeriveInput};
+
+use crate::{field_bindings, fields_pattern, ident_label, impl_fmt_trait, packed_locals, push_format_bounds, variant_format_args};
+use crate::{is_packed, ContainerAttrs, Context, Errors};
+
+// Generates `impl Display` from `#[display("...")]` on the struct, or on each variant of the enum.
+pub fn expand(ast: &DeriveInput) -> Result<TokenStream2, syn::Error> {
+    let container = ContainerAttrs::default();
+    let cx = Context {
+        generics: &ast.generics,
+        container: &container,
+        packed: matches!(ast.data, syn::Data::Struct(_)) && is_packed(&ast.attrs),
+    };
+
+    let mut wheres = vec![];
+    let mut errors = Errors::default();
+
+    let body = match &ast.data {
+        syn::Data::Struct(ds) => {
+            let arm = match parse_display_attr(&ast.attrs)? {
+                Some(fmt) => display_arm(quote!(Self), &fmt, &ds.fields, &cx, &mut wheres),
+                None => return Err(syn::Error::new_spanned(&ast.ident, "missing `#[display(\"...\")]`")),
+            };
+            quote!{
+                match self {
+                    #arm
+                }
+            }
+        }
+        syn::Data::Enum(de) => {
+            if let Some(attr) = ast.attrs.iter().find(|attr| attr.path.is_ident("display")) {
+                errors.push(syn::Error::new_spanned(attr, "`#[display(...)]` goes on each variant of an enum"));
+            }
+            let mut arms = vec![];
+            for variant in de.variants.iter() {
+                let vname = &variant.ident;
+                let fmt = match parse_display_attr(&variant.attrs) {
+                    Ok(fmt) => fmt,
+                    Err(err) => {
+                        errors.push(err);
+                        continue;
+                    }
+                };
+                match (fmt, &variant.fields) {
+                    (Some(fmt), fields) => arms.push(display_arm(quote!(Self::#vname), &fmt, fields, &cx, &mut wheres)),
+                    (None, syn::Fields::Unit) => {
+                        let label = ident_label(vname);
+                        arms.push(quote!(Self::#vname => f.write_str(#label),))
+                    }
+                    (None, _) => errors.push(syn::Error::new_spanned(vname, "missing `#[display(\"...\")]`")),
+                }
+            }
+            if arms.is_empty() {
+                quote!(match *self {})
+            } else {
+                quote!{
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        }
+        syn::Data::Union(du) => {
+            return Err(syn::Error::new(du.union_token.span, "CustomDisplay does not support unions"));
+        }
+    };
+    errors.finish()?;
+
+    Ok(impl_fmt_trait(ast, quote!(std::fmt::Display), wheres, body))
+}
+
+fn parse_display_attr(attrs: &[syn::Attribute]) -> Result<Option<syn::LitStr>, syn::Error> {
+    let mut result = None;
+    for attr in attrs.iter() {
+        if !attr.path.is_ident("display") {
+            continue;
+        }
+        match attr.parse_meta()? {
+            syn::Meta::List(list) if list.nested.len() == 1 => match list.nested.first() {
+                Some(syn::NestedMeta::Lit(syn::Lit::Str(s))) => result = Some(s.clone()),
+                _ => return Err(syn::Error::new_spanned(&list.nested, "expected a format string")),
+            },
+            meta => return Err(syn::Error::new_spanned(meta, "expected `display(\"...\")`")),
+        }
+    }
+    Ok(result)
+}
+
+// Generates the match arm writing the fields of a struct or variant at `path` with `fmt`.
+fn display_arm(path: TokenStream2, fmt: &syn::LitStr, fields: &syn::Fields, cx: &Context, wheres: &mut Vec<syn::WherePredicate>) -> TokenStream2 {
+    let bindings = field_bindings(fields);
+    let (args, used) = variant_format_args(fmt, fields, &bindings);
+    push_format_bounds(fmt, fields, cx, wheres);
+    if cx.packed {
+        let locals = packed_locals(fields, &used, quote!(self));
+        return quote!(_ => { #locals write!(f, #fmt #(, #args)*) });
+    }
+    let pattern = fields_pattern(path, fields, &used);
+    quote!(#pattern => write!(f, #fmt #(, #args)*),)
+}
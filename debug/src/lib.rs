@@ -1,182 +1,795 @@
 use proc_macro::TokenStream;
-use quote::quote;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
 use syn::{self, parse, DeriveInput};
 
+use crate::attr::{is_packed, parse_container_attrs, parse_field_attrs, parse_variant_attrs, ContainerAttrs, Errors, Redact};
+
+mod attr;
+mod display;
 
 #[proc_macro_derive(CustomDebug, attributes(debug))]
 pub fn derive(input: TokenStream) -> TokenStream {
-    let ast: DeriveInput = parse(input).unwrap();
-    let name = &ast.ident;
+    let ast: DeriveInput = match parse(input) {
+        Ok(ast) => ast,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    match expand(&ast) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
 
-    let mut debug_fields = vec![];
+fn expand(ast: &DeriveInput) -> Result<TokenStream2, syn::Error> {
+    let name = &ast.ident;
+    // every problem in the input is reported at once, not just the first one
+    let mut errors = Errors::default();
+    let container = errors.take(parse_container_attrs(&ast.attrs));
+    let cx = Context {
+        generics: &ast.generics,
+        container: &container,
+        packed: matches!(ast.data, syn::Data::Struct(_)) && is_packed(&ast.attrs),
+    };
 
-    let mut params = vec![];
     let mut wheres = vec![];
 
-    if let syn::Data::Struct(ds) = ast.data {
-        if let syn::Fields::Named(fields) = ds.fields {
-            for field in fields.named.iter() {
-                let field_ty = &field.ty;
-                let field_name = &field.ident;
-                if let Some(attr) = field.attrs.clone().iter().next() {
-                    match parse_debug_attr_value(&attr) {
-                        Ok(v) => {
-                            if let Some(value) = v {
-                                debug_fields.push(quote!{
-                                    field(stringify!(#field_name), &format_args!(#value, &self.#field_name))
-                                });
-                                continue;
-                            }
-                        },
-                        Err(err) => return err.to_compile_error().into(),
+    if let syn::Data::Union(du) = &ast.data {
+        // which field is set isn't known, none of them can be read
+        for field in du.fields.named.iter() {
+            for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("debug")) {
+                errors.push(syn::Error::new_spanned(attr, "union fields can't be formatted, use `#[debug(with = \"...\")]` on the union instead"));
+            }
+        }
+        if container.fields_fn {
+            errors.push(syn::Error::new(du.union_token.span, "`fields_fn` is not supported on unions"));
+        }
+    }
+
+    let body = match &ast.data {
+        _ if container.with.is_some() => {
+            // the fields aren't looked at, so they don't need any bounds, but their attributes are
+            // still checked
+            check_attrs(&ast.data, &mut errors);
+            let with = &container.with;
+            quote!(#with(self, f))
+        }
+        syn::Data::Struct(ds) => {
+            let arm = errors.take(debug_fields_arm(quote!(Self), name, &ds.fields, &cx, &mut wheres));
+            quote!{
+                match self {
+                    #arm
+                }
+            }
+        }
+        syn::Data::Enum(de) => {
+            let mut arms = vec![];
+            for variant in de.variants.iter() {
+                arms.push(errors.take(debug_variant_arm(variant, &cx, &mut wheres)));
+            }
+            if arms.is_empty() {
+                quote!(match *self {})
+            } else {
+                quote!{
+                    match self {
+                        #(#arms)*
                     }
                 }
-                debug_fields.push(quote!{
-                    field(stringify!(#field_name), &self.#field_name)
-                });
+            }
+        }
+        syn::Data::Union(_) => {
+            let elided = format!("{} {{ .. }}", ident_label(name));
+            quote!(f.write_str(#elided))
+        }
+    };
+    errors.finish()?;
 
-                let generics = &ast.generics;
+    // the inherent impl has bounds of its own, `with` on the container says nothing about it
+    let mut fields_wheres = vec![];
+    let mut field_impls = vec![];
+    let fields_fn = if container.fields_fn {
+        Some(debug_fields_body(ast, &cx, &mut fields_wheres, &mut field_impls)?)
+    } else {
+        None
+    };
+
+    // an explicit bound on the container replaces everything inferred from the fields
+    if let Some(bound) = &container.bound {
+        wheres = bound.clone();
+        fields_wheres = bound.clone();
+    }
 
-                if is_wrapped_generic_param(field_ty, &|ty| is_generic_param(ty, generics)) {
-                    if is_generic_param(field_ty, generics)  || is_debug_container(field_ty) {
-                        wheres.push(quote!(#field_ty: std::fmt::Debug));
-                    } else if let Some(tp) = unwrap_generics_param(field_ty, &|ty| is_generic_param(ty, generics)){
-                        let mut names = vec![];
-                        for seg in tp.path.segments.iter() {
-                            names.push(&seg.ident);
+    let mut tokens = impl_fmt_trait(ast, quote!(std::fmt::Debug), wheres, body);
+    if let Some(fields_body) = fields_fn {
+        let vis = &ast.vis;
+        let mut generics = ast.generics.clone();
+        generics.make_where_clause().predicates.extend(fields_wheres);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let field_impls = field_impls.iter().enumerate().map(|(k, body)| quote!{
+            impl #impl_generics std::fmt::Debug for __DebugField<#name #ty_generics, #k> #where_clause {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    #body
+                }
+            }
+        });
+        // A formatted field is printed through an adapter made on the spot, which a reference
+        // couldn't outlive. So every printed field gets a wrapper type of its own around the
+        // whole value, whose Debug impl picks out and writes that field, and the iterator hands
+        // out the value cast to the wrapper instead.
+        tokens.extend(quote!{
+            impl #impl_generics #name #ty_generics #where_clause {
+                /// The fields `Debug` prints, as pairs of their label and their formatted value.
+                #vis fn debug_fields(&self) -> impl Iterator<Item = (&'static str, &dyn std::fmt::Debug)> {
+                    #[repr(transparent)]
+                    struct __DebugField<T, const I: usize>(T);
+                    impl<T, const I: usize> __DebugField<T, I> {
+                        fn new(value: &T) -> &Self {
+                            // sound since the wrapper is `repr(transparent)` over `T`
+                            unsafe { &*(value as *const T as *const Self) }
                         }
-                        wheres.push(quote!(#(#names)::*: std::fmt::Debug));
                     }
+                    #(#field_impls)*
+                    #fields_body
                 }
             }
-        }
+        });
     }
-    for p in ast.generics.params.iter() {
-        if let syn::GenericParam::Type(t) = p {
-            let name = &t.ident;
-            let mut trait_bounds = vec![];
-            for b in t.bounds.iter() {
-                if let syn::TypeParamBound::Trait(tb) = b {
-                    let mut seg_idents = vec![];
-                    for seg in tb.path.segments.iter() {
-                        seg_idents.push(&seg.ident);
-                    }
-                    trait_bounds.push(quote!(#(#seg_idents)::*))
+    Ok(tokens)
+}
+
+// Parses the attributes of every variant and field only to report the errors in them.
+fn check_attrs(data: &syn::Data, errors: &mut Errors) {
+    let fields: Vec<&syn::Field> = match data {
+        syn::Data::Struct(ds) => ds.fields.iter().collect(),
+        syn::Data::Enum(de) => {
+            for variant in de.variants.iter() {
+                if let Err(err) = parse_variant_attrs(&variant.attrs) {
+                    errors.push(err);
                 }
             }
-            if trait_bounds.len() > 0 {
-                params.push(quote!(#name: #(#trait_bounds)+*));
-            } else {
-                params.push(quote!(#name));
-            }
+            de.variants.iter().flat_map(|variant| variant.fields.iter()).collect()
+        }
+        // attributes on union fields are errors of their own
+        syn::Data::Union(_) => vec![],
+    };
+    for field in fields {
+        if let Err(err) = parse_field_attrs(field) {
+            errors.push(err);
         }
     }
-    if params.len() > 0 {
-        let tokens = quote!{
-            impl<#(#params),*> std::fmt::Debug for #name<#(#params)*> where #(#wheres),* {
-                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                    f.debug_struct(stringify!(#name))
-                        .#(#debug_fields).*
-                        .finish()
+}
+
+// Generates the body of `debug_fields`, an iterator going through the printed fields by their
+// position among them, and into `impls` the body of `fmt` for the wrapper of each printed field.
+fn debug_fields_body(ast: &DeriveInput, cx: &Context, wheres: &mut Vec<syn::WherePredicate>, impls: &mut Vec<TokenStream2>) -> Result<TokenStream2, syn::Error> {
+    let name = &ast.ident;
+    let (arms, count) = match &ast.data {
+        syn::Data::Struct(ds) => {
+            let arm = debug_fields_fn_arm(quote!(Self), quote!(#name), &ds.fields, cx, wheres, impls)?;
+            (vec![arm], ds.fields.len())
+        }
+        syn::Data::Enum(de) => {
+            let mut arms = vec![];
+            for variant in de.variants.iter() {
+                let vname = &variant.ident;
+                if parse_variant_attrs(&variant.attrs)?.skip {
+                    let pattern = fields_pattern(quote!(Self::#vname), &variant.fields, &[]);
+                    arms.push(quote!(#pattern => None,));
+                } else {
+                    arms.push(debug_fields_fn_arm(quote!(Self::#vname), quote!(#name::#vname), &variant.fields, cx, wheres, impls)?);
+                }
+            }
+            (arms, de.variants.iter().map(|v| v.fields.len()).max().unwrap_or(0))
+        }
+        syn::Data::Union(du) => return Err(syn::Error::new(du.union_token.span, "`fields_fn` is not supported on unions")),
+    };
+    let matched = if arms.is_empty() { quote!(match *self {}) } else { quote!(match self { #(#arms)* }) };
+    Ok(quote!{
+        (0..#count).filter_map(move |__index| {
+            let __field: Option<(&'static str, &dyn std::fmt::Debug)> = #matched;
+            __field
+        })
+    })
+}
+
+// Generates the match arm giving the field of a struct or variant at `path` that's printed
+// `__index`th for `debug_fields`, tuple fields are labelled with their index. The field comes
+// as the whole value behind the wrapper numbered for it, whose `fmt` matches `named_path`.
+fn debug_fields_fn_arm(
+    path: TokenStream2,
+    named_path: TokenStream2,
+    fields: &syn::Fields,
+    cx: &Context,
+    wheres: &mut Vec<syn::WherePredicate>,
+    impls: &mut Vec<TokenStream2>,
+) -> Result<TokenStream2, syn::Error> {
+    let (used, values) = debug_field_values(fields, cx, wheres)?;
+    let indices: Vec<usize> = used.iter().enumerate().filter(|(_, binding)| binding.is_some()).map(|(i, _)| i).collect();
+    // only the fields with a `skip_if` are bound to pick the field
+    let mut skip_used = vec![None; used.len()];
+    let mut arms = vec![];
+    for (n, (&i, DebugField { label, value, skip_if })) in indices.iter().zip(values).enumerate() {
+        let mut field_used = vec![None; used.len()];
+        field_used[i] = used[i].clone();
+        let body = if cx.packed {
+            let locals = packed_locals(fields, &field_used, quote!(self.0));
+            quote!({ #locals std::fmt::Debug::fmt(#value, f) })
+        } else {
+            let pattern = fields_pattern(named_path.clone(), fields, &field_used);
+            quote!{
+                #[allow(unreachable_patterns)]
+                match &self.0 {
+                    #pattern => std::fmt::Debug::fmt(#value, f),
+                    _ => unreachable!(),
                 }
             }
         };
-        tokens.into()
+        let k = impls.len();
+        impls.push(body);
+
+        let label = label.unwrap_or_else(|| i.to_string());
+        let field = quote!(Some((#label, __DebugField::<Self, #k>::new(self) as &dyn std::fmt::Debug)));
+        arms.push(match skip_if {
+            Some(skip_if) => {
+                skip_used[i] = used[i].clone();
+                quote!(#n if !#skip_if => #field,)
+            }
+            None => quote!(#n => #field,),
+        });
+    }
+    let (pattern, locals) = if cx.packed {
+        (quote!(_), packed_locals(fields, &skip_used, quote!(self)))
     } else {
-        let tokens = quote!{
-            impl std::fmt::Debug for #name {
-                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                    f.debug_struct(stringify!(#name))
-                        .#(#debug_fields).*
-                        .finish()
-                }
+        (fields_pattern(path, fields, &skip_used), quote!())
+    };
+    Ok(quote!(#pattern => {
+        #locals
+        match __index {
+            #(#arms)*
+            _ => None,
+        }
+    }))
+}
+
+#[proc_macro_derive(CustomDisplay, attributes(display))]
+pub fn derive_display(input: TokenStream) -> TokenStream {
+    let ast: DeriveInput = match parse(input) {
+        Ok(ast) => ast,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    match display::expand(&ast) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+// Generates the impl of `fmt_trait` with `body` as its `fmt`, adding the inferred `wheres`
+// to the input's own generics.
+fn impl_fmt_trait(ast: &DeriveInput, fmt_trait: TokenStream2, wheres: Vec<syn::WherePredicate>, body: TokenStream2) -> TokenStream2 {
+    let name = &ast.ident;
+    let mut generics = ast.generics.clone();
+    generics.make_where_clause().predicates.extend(wheres);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote!{
+        impl #impl_generics #fmt_trait for #name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                #body
             }
+        }
+    }
+}
+
+// What generating the impl needs to know about the input as a whole.
+struct Context<'a> {
+    generics: &'a syn::Generics,
+    container: &'a ContainerAttrs,
+    // `#[repr(packed)]` structs have their fields copied out instead of borrowed
+    packed: bool,
+}
+
+// Generates the match arm formatting one enum variant.
+fn debug_variant_arm(variant: &syn::Variant, cx: &Context, wheres: &mut Vec<syn::WherePredicate>) -> Result<TokenStream2, syn::Error> {
+    let vname = &variant.ident;
+    let path = quote!(Self::#vname);
+    let vattrs = match parse_variant_attrs(&variant.attrs) {
+        Ok(vattrs) => vattrs,
+        Err(mut err) => {
+            // the fields may have errors of their own worth reporting along with these
+            if let Err(fields_err) = debug_fields_arm(path, vname, &variant.fields, cx, wheres) {
+                err.combine(fields_err);
+            }
+            return Err(err);
+        }
+    };
+
+    if vattrs.skip {
+        let label = ident_label(vname);
+        let elided = match &variant.fields {
+            syn::Fields::Named(_) => format!("{} {{ .. }}", label),
+            syn::Fields::Unnamed(_) => format!("{}(..)", label),
+            syn::Fields::Unit => label,
+        };
+        let pattern = fields_pattern(path, &variant.fields, &[]);
+        return Ok(quote!(#pattern => f.write_str(#elided),));
+    }
+
+    if let Some(fmt) = &vattrs.format {
+        let bindings = field_bindings(&variant.fields);
+        let (args, used) = variant_format_args(fmt, &variant.fields, &bindings);
+        push_format_bounds(fmt, &variant.fields, cx, wheres);
+        let pattern = fields_pattern(path, &variant.fields, &used);
+        return Ok(quote!(#pattern => write!(f, #fmt #(, #args)*),));
+    }
+
+    debug_fields_arm(path, vname, &variant.fields, cx, wheres)
+}
+
+// Generates the pattern matching a struct or variant at `path`, binding the fields marked as used.
+fn fields_pattern(path: TokenStream2, fields: &syn::Fields, used: &[Option<syn::Ident>]) -> TokenStream2 {
+    let bindings = (0..fields.len()).map(|i| match used.get(i) {
+        Some(Some(binding)) => quote!(#binding),
+        _ => quote!(_),
+    });
+    match fields {
+        syn::Fields::Named(fields) => {
+            let names = fields.named.iter().map(|f| &f.ident);
+            quote!(#path { #(#names: #bindings),* })
+        }
+        syn::Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        syn::Fields::Unit => quote!(#path),
+    }
+}
+
+// Generates the match arm formatting the fields of a struct or variant at `path` under `name`,
+// in the same shape `#[derive(Debug)]` uses.
+fn debug_fields_arm(path: TokenStream2, name: &syn::Ident, fields: &syn::Fields, cx: &Context, wheres: &mut Vec<syn::WherePredicate>) -> Result<TokenStream2, syn::Error> {
+    let (used, values) = debug_field_values(fields, cx, wheres)?;
+    let debug_fields = values.iter().map(|DebugField { label, value, skip_if }| {
+        let field = match label {
+            Some(label) => quote!(__builder.field(#label, #value);),
+            None => quote!(__builder.field(#value);),
         };
-        tokens.into()
+        match skip_if {
+            Some(skip_if) => quote!(if !#skip_if { #field }),
+            None => field,
+        }
+    });
+    let finish = if cx.container.non_exhaustive {
+        quote!(finish_non_exhaustive)
+    } else {
+        quote!(finish)
+    };
+    let name = ident_label(name);
+    let builder = match fields {
+        syn::Fields::Named(_) => quote!(f.debug_struct(#name)),
+        syn::Fields::Unnamed(_) => quote!(f.debug_tuple(#name)),
+        syn::Fields::Unit => quote!(f.write_str(#name)),
+    };
+    let body = match fields {
+        syn::Fields::Unit => builder,
+        _ => quote!({
+            let mut __builder = #builder;
+            #(#debug_fields)*
+            __builder.#finish()
+        }),
+    };
+    if cx.packed {
+        let locals = packed_locals(fields, &used, quote!(self));
+        return Ok(quote!(_ => { #locals #body }));
     }
+    let pattern = fields_pattern(path, fields, &used);
+    Ok(quote!(#pattern => #body,))
+}
+
+// Copies the fields of a packed struct bound in `used` to locals, since borrowing them could be
+// unaligned, out of `receiver`. Like with `#[derive(Debug)]`, this needs the fields to be Copy.
+fn packed_locals(fields: &syn::Fields, used: &[Option<syn::Ident>], receiver: TokenStream2) -> TokenStream2 {
+    let locals = fields.iter().zip(used).enumerate().filter_map(|(i, (field, binding))| {
+        let binding = binding.as_ref()?;
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(i.into()),
+        };
+        Some(quote!(let #binding = &{ #receiver.#member };))
+    });
+    quote!(#(#locals)*)
 }
 
-fn parse_debug_attr_value(attr: &syn::Attribute) -> Result<Option<syn::LitStr>, syn::Error> {
-    if let Some(seg) = attr.path.segments.first() {
-        if seg.ident != "debug" {
-            return Ok(None);
+// A field that gets printed.
+struct DebugField {
+    // `None` for tuple fields
+    label: Option<String>,
+    // an expression giving a `&impl Debug` for the field
+    value: TokenStream2,
+    // a `bool` expression, true when the field is left out this time
+    skip_if: Option<TokenStream2>,
+}
+
+// The fields of a struct or variant that get printed, along with the bindings their values use.
+fn debug_field_values(fields: &syn::Fields, cx: &Context, wheres: &mut Vec<syn::WherePredicate>) -> Result<(Vec<Option<syn::Ident>>, Vec<DebugField>), syn::Error> {
+    let bindings = field_bindings(fields);
+    let mut used = vec![];
+    let mut values = vec![];
+    let mut errors = Errors::default();
+    for (field, binding) in fields.iter().zip(bindings) {
+        let attrs = match parse_field_attrs(field) {
+            Ok(attrs) => attrs,
+            Err(err) => {
+                errors.push(err);
+                continue;
+            }
+        };
+        if attrs.skip {
+            used.push(None);
+            continue;
         }
-        let meta: syn::Meta = attr.parse_meta()?;
-        match meta {
-            syn::Meta::NameValue(values) => {
-                let arg_name = &values.path.segments.first().unwrap().ident;
-                if arg_name == "debug" {
-                    if let syn::Lit::Str(name) = values.lit {
-                        return Ok(Some(name));
+        if let Some(bound) = &attrs.bound {
+            for pred in bound.iter() {
+                push_bound(wheres, pred.clone());
+            }
+        }
+        let infer_bound = attrs.bound.is_none() && !attrs.no_bound;
+        let skip_if = match &attrs.skip_if {
+            Some(skip_if) => Some(quote!(#skip_if(#binding))),
+            None if cx.container.skip_defaults => is_default_check(&field.ty, &binding, cx.generics),
+            None => None,
+        };
+        let value = if let Some(redact) = &attrs.redact {
+            match redact {
+                Redact::Hidden => quote!(&format_args!("<redacted>")),
+                Redact::Len => quote!(&format_args!("<redacted len={}>", #binding.len())),
+            }
+        } else if let Some(limit) = &attrs.limit {
+            // the items print like a list, or a map, cut off after `limit` of them
+            if infer_bound {
+                push_field_bound(&field.ty, &syn::parse_quote!(std::fmt::Debug), cx, wheres);
+            }
+            // a field borrowing the collection is iterated through its own reference
+            let collection = match &field.ty {
+                syn::Type::Reference(_) => quote!(*#binding),
+                _ => quote!(#binding),
+            };
+            let (builder, rest) = if is_map_type(&field.ty) {
+                (quote!(f.debug_map()), quote!(entry(&format_args!("..."), &format_args!("({} more)", rest))))
+            } else {
+                (quote!(f.debug_list()), quote!(entry(&format_args!("... ({} more)", rest))))
+            };
+            debug_fn(quote!{{
+                let mut iter = std::iter::IntoIterator::into_iter(#collection);
+                let mut items = #builder;
+                items.entries(iter.by_ref().take(#limit));
+                let rest = iter.count();
+                if rest > 0 {
+                    items.#rest;
+                }
+                items.finish()
+            }})
+        } else if let Some(with) = &attrs.with {
+            // the field itself doesn't have to implement Debug
+            debug_fn(quote!(#with(#binding, f)))
+        } else if let Some(alt) = &attrs.alt {
+            // `{:#?}` reaches the field's formatter, the choice is made when it gets written
+            if infer_bound {
+                push_format_arg_bounds(alt, &FormatArg::Index(0), &field.ty, cx, wheres);
+            }
+            let compact = match &attrs.format {
+                Some(fmt) => {
+                    if infer_bound {
+                        push_format_arg_bounds(fmt, &FormatArg::Index(0), &field.ty, cx, wheres);
+                    }
+                    quote!(write!(f, #fmt, #binding))
+                }
+                None => {
+                    if infer_bound {
+                        push_field_bound(&field.ty, &syn::parse_quote!(std::fmt::Debug), cx, wheres);
                     }
+                    quote!(std::fmt::Debug::fmt(#binding, f))
+                }
+            };
+            debug_fn(quote!{
+                if f.alternate() {
+                    write!(f, #alt, #binding)
                 } else {
-                    let start = attr.path.segments.first().unwrap().ident.span();
-                    return Err(syn::Error::new(start, "expected `debug = \"...\"`".to_owned()));
+                    #compact
                 }
-            },
-            _ => {
-                return Err(syn::Error::new(attr.bracket_token.span, "expected `debug = `\"...\"`".to_owned()));
-            },
-        }
+            })
+        } else if let Some(fmt) = &attrs.format {
+            if infer_bound {
+                push_format_arg_bounds(fmt, &FormatArg::Index(0), &field.ty, cx, wheres);
+            }
+            quote!(&format_args!(#fmt, #binding))
+        } else {
+            if infer_bound {
+                push_field_bound(&field.ty, &syn::parse_quote!(std::fmt::Debug), cx, wheres);
+            }
+            quote!(#binding)
+        };
+        let label = match (&attrs.rename, &field.ident) {
+            (Some(label), _) => Some(label.value()),
+            (None, Some(field_name)) => {
+                let label = ident_label(field_name);
+                match &cx.container.rename_all {
+                    Some(rule) => Some(rule.apply(&label)),
+                    None => Some(label),
+                }
+            }
+            (None, None) => None,
+        };
+        values.push(DebugField { label, value, skip_if });
+        used.push(Some(binding));
     }
-    return Ok(None);
+
+    errors.finish()?;
+    Ok((used, values))
 }
 
-// returns true if generic param wrapped via others like PhantomData
-fn is_wrapped_generic_param(field_ty: &syn::Type, is_generic_param: &dyn Fn(&syn::Type) -> bool) -> bool {
-    if let Some(_) = unwrap_generics_param(field_ty, is_generic_param) {
-        return true;
+// Generates the `bool` expression telling if the field bound to `binding` holds its type's
+// default value, for the types whose default is known without needing any bounds.
+fn is_default_check(ty: &syn::Type, binding: &syn::Ident, generics: &syn::Generics) -> Option<TokenStream2> {
+    match ty {
+        syn::Type::Reference(r) => match &*r.elem {
+            syn::Type::Slice(_) => Some(quote!(#binding.is_empty())),
+            syn::Type::Path(p) if p.path.is_ident("str") => Some(quote!(#binding.is_empty())),
+            _ => None,
+        },
+        syn::Type::Path(p) if p.qself.is_none() && !is_generic_param(&p.path, generics) => {
+            let name = p.path.segments.last()?.ident.to_string();
+            match name.as_str() {
+                "Option" => Some(quote!(#binding.is_none())),
+                "String" | "Vec" | "VecDeque" | "LinkedList" | "BinaryHeap" | "HashMap" | "BTreeMap" | "HashSet" | "BTreeSet" | "IndexMap" | "IndexSet" => {
+                    Some(quote!(#binding.is_empty()))
+                }
+                "bool" => Some(quote!(!*#binding)),
+                "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => Some(quote!((*#binding == 0))),
+                "f32" | "f64" => Some(quote!((*#binding == 0.0))),
+                _ => None,
+            }
+        }
+        _ => None,
     }
-    false
 }
 
+// The name an identifier is printed with, without the `r#` of raw identifiers.
+fn ident_label(ident: &syn::Ident) -> String {
+    syn::ext::IdentExt::unraw(ident).to_string()
+}
+
+// Wraps `write`, an expression writing to the formatter `f`, into a reference to a value
+// implementing Debug.
+fn debug_fn(write: TokenStream2) -> TokenStream2 {
+    let value = debug_fn_value(write);
+    quote!(&#value)
+}
 
-fn is_generic_param(field_ty: &syn::Type, generics: &syn::Generics) -> bool {
-    if let syn::Type::Path(ty) = field_ty {
-        if ty.path.segments.len() == 0 {
-            return false;
+// Wraps `write` into a value implementing Debug, which holds on to the bindings it uses.
+fn debug_fn_value(write: TokenStream2) -> TokenStream2 {
+    quote!({
+        struct DebugFn<F: Fn(&mut std::fmt::Formatter<'_>) -> std::fmt::Result>(F);
+        impl<F: Fn(&mut std::fmt::Formatter<'_>) -> std::fmt::Result> std::fmt::Debug for DebugFn<F> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                (self.0)(f)
+            }
         }
-        let name = ty.path.segments.first().unwrap();
+        DebugFn(move |f: &mut std::fmt::Formatter<'_>| #write)
+    })
+}
 
-        for p in generics.params.iter() {
-            if let syn::GenericParam::Type(t) = p {
-                if t.ident == name.ident {
-                    return true
+// Local names the fields are bound to when matching, prefixed so they can't shadow the formatter.
+fn field_bindings(fields: &syn::Fields) -> Vec<syn::Ident> {
+    fields.iter().enumerate().map(|(i, field)| {
+        match &field.ident {
+            Some(ident) => format_ident!("__self_{}", syn::ext::IdentExt::unraw(ident)),
+            None => format_ident!("__self_{}", i),
+        }
+    }).collect()
+}
+
+// Builds the arguments for a variant level format string: named fields are passed by name,
+// tuple fields positionally up to the highest index the string uses. Also returns which
+// bindings the arguments use.
+fn variant_format_args(fmt: &syn::LitStr, fields: &syn::Fields, bindings: &[syn::Ident]) -> (Vec<TokenStream2>, Vec<Option<syn::Ident>>) {
+    let refs = format_string_args(&fmt.value());
+    let positional = refs.iter().filter_map(|(arg, _)| match arg {
+        FormatArg::Index(i) => Some(i + 1),
+        FormatArg::Named(_) => None,
+    }).max().unwrap_or(0);
+    let mut args = vec![];
+    let mut used = vec![];
+    for (i, (field, binding)) in fields.iter().zip(bindings.iter()).enumerate() {
+        let uses = match &field.ident {
+            Some(ident) => {
+                let ident = syn::ext::IdentExt::unraw(ident);
+                let uses = refs.iter().any(|(arg, _)| *arg == FormatArg::Named(ident.to_string()));
+                if uses {
+                    args.push(quote!(#ident = #binding));
                 }
+                uses
             }
-        }
+            None => {
+                let uses = i < positional;
+                if uses {
+                    args.push(quote!(#binding));
+                }
+                uses
+            }
+        };
+        used.push(Some(binding.clone()).filter(|_| uses));
     }
-    return false;
+    (args, used)
 }
 
-fn is_debug_container(ty: &syn::Type) -> bool {
-    if let syn::Type::Path(tp) = ty {
-        if tp.path.segments.len() == 0 {
-            return false;
+// Adds the bounds for the traits a struct or variant level format string writes each field through.
+fn push_format_bounds(fmt: &syn::LitStr, fields: &syn::Fields, cx: &Context, wheres: &mut Vec<syn::WherePredicate>) {
+    for (i, field) in fields.iter().enumerate() {
+        let arg = match &field.ident {
+            Some(ident) => FormatArg::Named(syn::ext::IdentExt::unraw(ident).to_string()),
+            None => FormatArg::Index(i),
+        };
+        push_format_arg_bounds(fmt, &arg, &field.ty, cx, wheres);
+    }
+}
+
+// Adds the bounds for the traits the format string writes the argument `arg` of type `ty` through.
+fn push_format_arg_bounds(fmt: &syn::LitStr, arg: &FormatArg, ty: &syn::Type, cx: &Context, wheres: &mut Vec<syn::WherePredicate>) {
+    for (r, trait_name) in format_string_args(&fmt.value()) {
+        match trait_name {
+            // a reference's address is printed without involving the referent
+            Some("Pointer") => {}
+            Some(trait_name) if r == *arg => {
+                let fmt_trait = format_ident!("{}", trait_name);
+                push_field_bound(ty, &syn::parse_quote!(std::fmt::#fmt_trait), cx, wheres);
+            }
+            _ => {}
         }
-        return tp.path.segments.first().unwrap().ident == "PhantomData";
     }
-    return false;
 }
 
+// An argument a format string refers to.
+#[derive(PartialEq)]
+enum FormatArg {
+    Named(String),
+    Index(usize),
+}
 
-fn unwrap_generics_param<'a>(field_ty: &'a syn::Type, is_generic_param: &dyn Fn(&'a syn::Type) -> bool) -> Option<&'a syn::TypePath> {
-    if is_generic_param(field_ty) {
-        if let syn::Type::Path(ret) = field_ty {
-            return Some(ret);
+// Scans a format string and returns every argument it refers to, along with the name of the
+// formatting trait it is written through, or `None` when it's only used as a width or precision.
+fn format_string_args(fmt: &str) -> Vec<(FormatArg, Option<&'static str>)> {
+    let mut refs = vec![];
+    let mut next_implicit = 0;
+    let mut arg_ref = |arg: &str| {
+        if arg.is_empty() || arg == "*" {
+            next_implicit += 1;
+            FormatArg::Index(next_implicit - 1)
+        } else {
+            match arg.parse::<usize>() {
+                Ok(i) => FormatArg::Index(i),
+                Err(_) => FormatArg::Named(arg.to_owned()),
+            }
+        }
+    };
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '}' {
+            chars.next_if_eq(&'}');
+            continue;
         }
+        if c != '{' || chars.next_if_eq(&'{').is_some() {
+            continue;
+        }
+        let spec: String = chars.by_ref().take_while(|c| *c != '}').collect();
+        let (arg, options) = match spec.find(':') {
+            Some(i) => (spec[..i].trim(), spec[i + 1..].trim()),
+            None => (spec.trim(), ""),
+        };
+        // width and precision can refer to arguments too, as `name$`, `1$` or `.*`
+        for part in options.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$' || c == '*')) {
+            if let Some(arg) = part.strip_suffix('$') {
+                refs.push((arg_ref(arg), None));
+            } else if part == "*" {
+                refs.push((arg_ref(part), None));
+            }
+        }
+        let trait_name = match options.chars().last() {
+            Some('?') => "Debug",
+            Some('x') => "LowerHex",
+            Some('X') => "UpperHex",
+            Some('o') => "Octal",
+            Some('b') => "Binary",
+            Some('e') => "LowerExp",
+            Some('E') => "UpperExp",
+            Some('p') => "Pointer",
+            _ => "Display",
+        };
+        refs.push((arg_ref(arg), Some(trait_name)));
     }
+    refs
+}
 
-    if let syn::Type::Path(ty) = field_ty {
-        for seg in ty.path.segments.iter() {
-            if let syn::PathArguments::AngleBracketed(args) = &seg.arguments {
-                for item in args.args.iter() {
-                    if let syn::GenericArgument::Type(ty) = item {
-                        return unwrap_generics_param(ty, is_generic_param);
+// Adds the bounds on the trait `bound` a field of type `field_ty` needs: one for every type
+// parameter it mentions and one for every associated type of a type parameter it mentions.
+fn push_field_bound(field_ty: &syn::Type, bound: &syn::Path, cx: &Context, wheres: &mut Vec<syn::WherePredicate>) {
+    match field_ty {
+        syn::Type::Path(ty) => {
+            if let Some(qself) = &ty.qself {
+                // `<T as Trait>::Assoc` is bounded as a whole when its self type mentions a parameter
+                let mut inner = vec![];
+                push_field_bound(&qself.ty, bound, cx, &mut inner);
+                if !inner.is_empty() {
+                    push_bound(wheres, syn::parse_quote!(#ty: #bound));
+                    return;
+                }
+            } else if is_generic_param(&ty.path, cx.generics) {
+                // either `T` itself, or `T::Assoc` in which case `T` doesn't need to be Debug
+                push_bound(wheres, syn::parse_quote!(#ty: #bound));
+                return;
+            }
+            if is_debug_container(&ty.path, &cx.container.phantom) {
+                return;
+            }
+            for seg in ty.path.segments.iter() {
+                if let syn::PathArguments::AngleBracketed(args) = &seg.arguments {
+                    for item in args.args.iter() {
+                        match item {
+                            syn::GenericArgument::Type(ty) => push_field_bound(ty, bound, cx, wheres),
+                            syn::GenericArgument::Binding(binding) => push_field_bound(&binding.ty, bound, cx, wheres),
+                            _ => {}
+                        }
                     }
                 }
             }
         }
+        syn::Type::Tuple(ty) => {
+            for elem in ty.elems.iter() {
+                push_field_bound(elem, bound, cx, wheres);
+            }
+        }
+        syn::Type::Array(ty) => push_field_bound(&ty.elem, bound, cx, wheres),
+        syn::Type::Slice(ty) => push_field_bound(&ty.elem, bound, cx, wheres),
+        syn::Type::Reference(ty) => push_field_bound(&ty.elem, bound, cx, wheres),
+        syn::Type::Paren(ty) => push_field_bound(&ty.elem, bound, cx, wheres),
+        syn::Type::Group(ty) => push_field_bound(&ty.elem, bound, cx, wheres),
+        // raw pointers print their address, anything else doesn't implement Debug through its parameters
+        _ => {}
+    }
+}
+
+// Adds `pred` unless an identical predicate is already there.
+fn push_bound(wheres: &mut Vec<syn::WherePredicate>, pred: syn::WherePredicate) {
+    let key = quote!(#pred).to_string();
+    if !wheres.iter().any(|w| quote!(#w).to_string() == key) {
+        wheres.push(pred);
+    }
+}
+
+// returns true if the path starts with one of the type parameters, like `T` or `T::Value`
+fn is_generic_param(path: &syn::Path, generics: &syn::Generics) -> bool {
+    if path.leading_colon.is_some() {
+        return false;
+    }
+    let first = match path.segments.first() {
+        Some(seg) if seg.arguments.is_empty() => seg,
+        _ => return false,
+    };
+    generics.type_params().any(|t| t.ident == first.ident)
+}
+
+// returns true for the standard maps and IndexMap, or a reference to one, which iterate as
+// key value pairs
+fn is_map_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(ty) => is_map_type(&ty.elem),
+        syn::Type::Path(ty) => match ty.path.segments.last() {
+            Some(seg) => seg.ident == "HashMap" || seg.ident == "BTreeMap" || seg.ident == "IndexMap",
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+// returns true for wrappers like PhantomData that are Debug whatever their parameters are,
+// `phantom` lists the ones declared with `#[debug(phantom(...))]`
+fn is_debug_container(path: &syn::Path, phantom: &[syn::Ident]) -> bool {
+    match path.segments.last() {
+        Some(seg) => seg.ident == "PhantomData" || phantom.contains(&seg.ident),
+        None => false,
     }
-    return None;
 }
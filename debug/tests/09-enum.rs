@@ -0,0 +1,70 @@
+// Enums are formatted variant by variant in the same shape as the standard
+// library's derive: unit variants print their name, tuple variants go through
+// debug_tuple and struct variants through debug_struct. Field level formats
+// work inside variants just like on struct fields.
+//
+// A variant can also be given a format string of its own, which refers to the
+// variant's fields by name (or by position for tuple variants), or be marked
+// #[debug(skip)] to hide its fields altogether. The fields a variant's format
+// string uses get bounds for the traits it writes them through.
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+pub enum Shape<T> {
+    Empty,
+    Circle(T),
+    Rect {
+        width: T,
+        #[debug = "0b{:08b}"]
+        flags: u8,
+    },
+    #[debug = "Point({x}, {y})"]
+    Point { x: i32, y: i32 },
+    #[debug = "Pair<{0}, {1:?}>"]
+    Pair(u8, &'static str),
+    #[debug(skip)]
+    Secret { key: &'static str },
+    #[debug(skip)]
+    Opaque(u32),
+}
+
+#[derive(Debug)]
+pub enum Expected<T> {
+    Empty,
+    Circle(T),
+    Rect { width: T, flags: u8 },
+}
+
+#[derive(CustomDebug)]
+pub enum Tagged<T, U> {
+    #[debug = "Debug({0:?})"]
+    Debug(T),
+    #[debug = "Hex({value:#x})"]
+    Hex { value: U },
+    Untagged,
+}
+
+#[derive(CustomDebug)]
+pub enum Never {}
+
+fn main() {
+    assert_eq!(format!("{:?}", Shape::<u8>::Empty), format!("{:?}", Expected::<u8>::Empty));
+    assert_eq!(format!("{:?}", Shape::Circle(1.5)), format!("{:?}", Expected::Circle(1.5)));
+    assert_eq!(format!("{:#?}", Shape::Circle(1.5)), format!("{:#?}", Expected::Circle(1.5)));
+
+    let rect = Shape::Rect { width: 3, flags: 0b101 };
+    assert_eq!(format!("{:?}", rect), "Rect { width: 3, flags: 0b00000101 }");
+
+    assert_eq!(format!("{:?}", Shape::<u8>::Point { x: 1, y: -2 }), "Point(1, -2)");
+    assert_eq!(format!("{:?}", Shape::<u8>::Pair(7, "seven")), r#"Pair<7, "seven">"#);
+    assert_eq!(format!("{:?}", Shape::<u8>::Secret { key: "hunter2" }), "Secret { .. }");
+    assert_eq!(format!("{:?}", Shape::<u8>::Opaque(42)), "Opaque(..)");
+
+    assert_eq!(format!("{:?}", Tagged::<_, u32>::Debug("a")), r#"Debug("a")"#);
+    assert_eq!(format!("{:?}", Tagged::<(), _>::Hex { value: 255u32 }), "Hex(0xff)");
+    assert_eq!(format!("{:?}", Tagged::<(), u32>::Untagged), "Untagged");
+
+    fn assert_debug<F: std::fmt::Debug>() {}
+    assert_debug::<Never>();
+}
@@ -0,0 +1,25 @@
+// Tuple structs print through debug_tuple and unit structs print just their
+// name, matching #[derive(Debug)]. Fields of a tuple struct accept the same
+// #[debug = "..."] attribute as named fields.
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+pub struct Meters(#[debug = "{:.2}"] f64);
+
+#[derive(CustomDebug)]
+pub struct Pair<T>(T, &'static str);
+
+#[derive(CustomDebug)]
+pub struct Marker;
+
+#[derive(CustomDebug)]
+pub struct Empty {}
+
+fn main() {
+    assert_eq!(format!("{:?}", Meters(1.0 / 3.0)), "Meters(0.33)");
+    assert_eq!(format!("{:?}", Pair(1u8, "one")), r#"Pair(1, "one")"#);
+    assert_eq!(format!("{:#?}", Pair(1u8, "one")), "Pair(\n    1,\n    \"one\",\n)");
+    assert_eq!(format!("{:?}", Marker), "Marker");
+    assert_eq!(format!("{:?}", Empty {}), "Empty");
+}
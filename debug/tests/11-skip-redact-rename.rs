@@ -0,0 +1,53 @@
+// Fields can be kept out of the output with #[debug(skip)], have their value
+// hidden with #[debug(redact)] (or replaced by a length hint with
+// #[debug(redact(len))]), and be printed under another label with
+// #[debug(rename = "...")]. Skipped and redacted fields don't need to
+// implement Debug at all.
+
+use derive_debug::CustomDebug;
+
+struct NotDebug;
+
+#[derive(CustomDebug)]
+pub struct Credentials {
+    #[debug(rename = "user")]
+    username: String,
+    #[debug(redact)]
+    password: String,
+    #[debug(redact(len))]
+    token: Vec<u8>,
+    #[debug(skip)]
+    cache: NotDebug,
+}
+
+#[derive(CustomDebug)]
+pub struct Frame(u16, #[debug(skip)] NotDebug, #[debug(redact)] Vec<u8>);
+
+#[derive(CustomDebug)]
+pub enum Event {
+    Login {
+        #[debug(rename = "who")]
+        user: &'static str,
+        #[debug(redact)]
+        secret: &'static str,
+    },
+}
+
+fn main() {
+    let credentials = Credentials {
+        username: "ferris".to_owned(),
+        password: "hunter2".to_owned(),
+        token: vec![0; 12],
+        cache: NotDebug,
+    };
+    assert_eq!(
+        format!("{:?}", credentials),
+        r#"Credentials { user: "ferris", password: <redacted>, token: <redacted len=12> }"#,
+    );
+
+    let frame = Frame(7, NotDebug, vec![1, 2, 3]);
+    assert_eq!(format!("{:?}", frame), "Frame(7, <redacted>)");
+
+    let event = Event::Login { user: "ferris", secret: "hunter2" };
+    assert_eq!(format!("{:?}", event), r#"Login { who: "ferris", secret: <redacted> }"#);
+}
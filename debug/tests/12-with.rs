@@ -0,0 +1,54 @@
+// A field can name a function of its own to be formatted with, using
+// #[debug(with = "path::to::function")]. The function has the signature
+// fn(&T, &mut fmt::Formatter) -> fmt::Result, which allows rendering values
+// in ways a format string can't express, such as bytes as hex.
+//
+// Because the function does the formatting, the field type doesn't need to
+// implement Debug and no bound is inferred for it.
+
+use derive_debug::CustomDebug;
+use std::fmt::Debug;
+
+mod render {
+    use std::fmt;
+
+    pub fn hex_bytes(bytes: &Vec<u8>, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in bytes {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+
+    pub fn opaque<T>(_: &T, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<opaque>")
+    }
+}
+
+#[derive(CustomDebug)]
+pub struct Packet<T> {
+    id: u32,
+    #[debug(with = "render::hex_bytes")]
+    payload: Vec<u8>,
+    #[debug(with = "render::opaque")]
+    state: T,
+}
+
+#[derive(CustomDebug)]
+pub struct Digest(#[debug(with = "render::hex_bytes")] Vec<u8>);
+
+fn assert_debug<F: Debug>() {}
+
+fn main() {
+    // Does not implement Debug.
+    struct NotDebug;
+
+    assert_debug::<Packet<NotDebug>>();
+
+    let packet = Packet {
+        id: 7,
+        payload: vec![0xde, 0xad, 0xbe, 0xef],
+        state: NotDebug,
+    };
+    assert_eq!(format!("{:?}", packet), "Packet { id: 7, payload: deadbeef, state: <opaque> }");
+    assert_eq!(format!("{:?}", Digest(vec![1, 2, 255])), "Digest(0102ff)");
+}
@@ -0,0 +1,65 @@
+// When the inferred bounds are wrong, they can be replaced. A container level
+// #[debug(bound = "...")] replaces all of the inferred where-predicates, and
+// an empty bound = "" turns inference off entirely. On a field, the same
+// option replaces only the predicates that field would have contributed.
+
+use derive_debug::CustomDebug;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+
+// Debug for any T, without requiring T: Debug.
+pub struct Tag<T>(PhantomData<T>);
+
+impl<T> Debug for Tag<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Tag")
+    }
+}
+
+// Debug only when T: Clone.
+pub struct Cloned<T>(PhantomData<T>);
+
+impl<T: Clone> Debug for Cloned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Cloned")
+    }
+}
+
+#[derive(CustomDebug)]
+#[debug(bound = "")]
+pub struct Opaque<T> {
+    tag: Tag<T>,
+}
+
+#[derive(CustomDebug)]
+#[debug(bound = "T: Clone")]
+pub enum Either<T> {
+    Left(Tag<T>),
+    Right(Cloned<T>),
+}
+
+#[derive(CustomDebug)]
+pub struct Holder<T> {
+    #[debug(bound = "T: Clone")]
+    inner: Cloned<T>,
+    #[debug(bound = "")]
+    tag: Tag<T>,
+}
+
+fn assert_debug<F: Debug>() {}
+
+fn main() {
+    // Does not implement Debug.
+    #[derive(Clone)]
+    struct NotDebug;
+
+    assert_debug::<Opaque<NotDebug>>();
+    assert_debug::<Either<NotDebug>>();
+    assert_debug::<Holder<NotDebug>>();
+
+    let holder = Holder::<NotDebug> {
+        inner: Cloned(PhantomData),
+        tag: Tag(PhantomData),
+    };
+    assert_eq!(format!("{:?}", holder), "Holder { inner: Cloned, tag: Tag }");
+}
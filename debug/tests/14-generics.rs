@@ -0,0 +1,53 @@
+// The impl has to carry over every kind of generic parameter the input has:
+// lifetimes, const generics, several type parameters with arbitrary bounds
+// (including generic traits and ?Sized), and predicates from an existing where
+// clause. The inferred Debug bounds are added to that where clause.
+
+use derive_debug::CustomDebug;
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+#[derive(CustomDebug)]
+pub struct Borrowed<'a, 'b: 'a> {
+    name: Cow<'a, str>,
+    tag: &'b str,
+}
+
+#[derive(CustomDebug)]
+pub struct Buffer<T, const N: usize> {
+    data: [u8; N],
+    value: T,
+}
+
+#[derive(CustomDebug)]
+pub struct Pair<K: Into<String> + Clone, V: ?Sized> {
+    key: K,
+    value: Box<V>,
+}
+
+#[derive(CustomDebug)]
+pub enum Choice<A, B = u8>
+where
+    A: Default,
+{
+    First(A),
+    Second(B),
+}
+
+fn assert_debug<F: Debug>() {}
+
+fn main() {
+    assert_debug::<Borrowed>();
+    assert_debug::<Buffer<u8, 4>>();
+    assert_debug::<Pair<String, str>>();
+    assert_debug::<Choice<u8>>();
+
+    let buffer = Buffer { data: [1, 2], value: "x" };
+    assert_eq!(format!("{:?}", buffer), r#"Buffer { data: [1, 2], value: "x" }"#);
+
+    let pair: Pair<&str, str> = Pair { key: "k", value: "v".into() };
+    assert_eq!(format!("{:?}", pair), r#"Pair { key: "k", value: "v" }"#);
+
+    let choice: Choice<u8, &str> = Choice::Second("two");
+    assert_eq!(format!("{:?}", choice), r#"Second("two")"#);
+}
@@ -0,0 +1,55 @@
+// Associated types are found wherever they appear in a field type: nested in
+// generic arguments, tuples, arrays, slices and references, or written as a
+// qualified path like <T as Trait>::Value. Each distinct associated type gets
+// exactly one Debug bound, however many fields mention it, and the type
+// parameter itself is not required to be Debug.
+
+use derive_debug::CustomDebug;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+pub trait Trait {
+    type Value;
+    type Other;
+}
+
+#[derive(CustomDebug)]
+pub struct Field<'a, T: Trait, U: Trait> {
+    map: HashMap<String, Vec<T::Value>>,
+    pair: (T::Value, U::Other),
+    array: [Option<T::Value>; 2],
+    slice: &'a [U::Other],
+    qualified: Box<<T as Trait>::Other>,
+}
+
+fn assert_debug<F: Debug>() {}
+
+fn main() {
+    // Do not implement Debug, but their associated types do.
+    struct Id;
+    struct Name;
+
+    impl Trait for Id {
+        type Value = u8;
+        type Other = u16;
+    }
+
+    impl Trait for Name {
+        type Value = String;
+        type Other = &'static str;
+    }
+
+    assert_debug::<Field<Id, Name>>();
+
+    let field = Field::<Id, Name> {
+        map: HashMap::new(),
+        pair: (1, "one"),
+        array: [Some(2), None],
+        slice: &["a"],
+        qualified: Box::new(3),
+    };
+    assert_eq!(
+        format!("{:?}", field),
+        r#"Field { map: {}, pair: (1, "one"), array: [Some(2), None], slice: ["a"], qualified: 3 }"#,
+    );
+}
@@ -0,0 +1,55 @@
+// Like PhantomData, zero sized marker wrappers of our own are Debug whatever
+// their type parameter is. #[debug(phantom(...))] on the container names such
+// wrappers so no T: Debug bound is inferred through them, and #[debug(no_bound)]
+// on a single field turns bound inference off for that field.
+
+use derive_debug::CustomDebug;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+
+pub struct Tagged<T>(PhantomData<T>);
+
+impl<T> Debug for Tagged<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Tagged")
+    }
+}
+
+pub struct TypeId<T>(PhantomData<T>);
+
+impl<T> Debug for TypeId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TypeId")
+    }
+}
+
+#[derive(CustomDebug)]
+#[debug(phantom(Tagged, TypeId))]
+pub struct Handle<T, U> {
+    tag: Tagged<T>,
+    id: Option<TypeId<U>>,
+    value: u8,
+}
+
+#[derive(CustomDebug)]
+pub struct Marked<T> {
+    #[debug(no_bound)]
+    tag: Tagged<T>,
+}
+
+fn assert_debug<F: Debug>() {}
+
+fn main() {
+    // Does not implement Debug.
+    struct NotDebug;
+
+    assert_debug::<Handle<NotDebug, NotDebug>>();
+    assert_debug::<Marked<NotDebug>>();
+
+    let handle = Handle::<NotDebug, NotDebug> {
+        tag: Tagged(PhantomData),
+        id: None,
+        value: 1,
+    };
+    assert_eq!(format!("{:?}", handle), "Handle { tag: Tagged, id: None, value: 1 }");
+}
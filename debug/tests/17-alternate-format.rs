@@ -0,0 +1,58 @@
+// A field can have a separate format for the alternate {:#?} form, given as
+// #[debug(fmt = "...", alt = "...")]. With only alt, the compact form falls
+// back to the field's own Debug impl. Fields without a custom format keep
+// seeing the alternate flag, so nested values are pretty printed just like
+// with #[derive(Debug)].
+//
+// Like the variant level ones, field formats bound a type parameter by the
+// traits they write the field through.
+
+use derive_debug::CustomDebug;
+
+#[derive(Debug)]
+pub struct Inner {
+    a: u8,
+}
+
+#[derive(CustomDebug)]
+pub struct Register {
+    #[debug(fmt = "{:x}", alt = "{:#010x}")]
+    value: u32,
+    #[debug(alt = "{:#?}!")]
+    label: &'static str,
+    inner: Inner,
+}
+
+#[derive(CustomDebug)]
+pub struct Generic<T, U> {
+    #[debug(alt = "{:#x}")]
+    hex: T,
+    #[debug(fmt = "{:?}", alt = "{:#?}")]
+    nested: U,
+}
+
+fn main() {
+    let register = Register {
+        value: 0xbeef,
+        label: "status",
+        inner: Inner { a: 1 },
+    };
+
+    assert_eq!(
+        format!("{:?}", register),
+        r#"Register { value: beef, label: "status", inner: Inner { a: 1 } }"#,
+    );
+
+    let expected = r#"Register {
+    value: 0x0000beef,
+    label: "status"!,
+    inner: Inner {
+        a: 1,
+    },
+}"#;
+    assert_eq!(format!("{:#?}", register), expected);
+
+    let generic = Generic { hex: 255u8, nested: Some(1) };
+    assert_eq!(format!("{:?}", generic), "Generic { hex: 255, nested: Some(1) }");
+    assert_eq!(format!("{:#?}", generic), "Generic {\n    hex: 0xff,\n    nested: Some(\n        1,\n    ),\n}");
+}
@@ -0,0 +1,50 @@
+// CustomDisplay is a sibling derive generating Display from a format string
+// given as #[display("...")] on the struct, or on each variant of an enum.
+// The format string refers to fields by name, or by position for tuple
+// fields. Unit variants without an attribute print their name.
+//
+// Bounds are inferred the same way as for CustomDebug, using the formatting
+// trait each field is written with: {} needs Display, {:?} needs Debug, {:x}
+// needs LowerHex and so on.
+
+use derive_debug::{CustomDebug, CustomDisplay};
+use std::fmt::{Debug, Display};
+
+#[derive(CustomDisplay)]
+#[display("{name} ({id:#x})")]
+pub struct User {
+    id: u32,
+    name: String,
+}
+
+#[derive(CustomDisplay)]
+#[display("{0}/{1}")]
+pub struct Ratio(u32, u32);
+
+#[derive(CustomDebug, CustomDisplay)]
+pub enum Message<T> {
+    Ping,
+    #[display("text: {0}")]
+    Text(T),
+    #[display("move to {x}, {y} ({tag:?})")]
+    Move { x: i32, y: i32, tag: Option<T> },
+}
+
+fn assert_debug<F: Debug>() {}
+
+fn assert_display<F: Display>() {}
+
+fn main() {
+    let user = User { id: 255, name: "ferris".to_owned() };
+    assert_eq!(user.to_string(), "ferris (0xff)");
+    assert_eq!(Ratio(3, 4).to_string(), "3/4");
+
+    assert_eq!(Message::<u8>::Ping.to_string(), "Ping");
+    assert_eq!(Message::Text("hi").to_string(), "text: hi");
+    assert_eq!(Message::Move { x: 1, y: -1, tag: Some("t") }.to_string(), r#"move to 1, -1 (Some("t"))"#);
+    assert_eq!(format!("{:?}", Message::Text(1)), "Text(1)");
+
+    // T: Display + Debug, as required by the format strings.
+    assert_display::<Message<&str>>();
+    assert_debug::<Message<&str>>();
+}
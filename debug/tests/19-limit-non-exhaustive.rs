@@ -0,0 +1,62 @@
+// Large collections can be cut short with #[debug(limit = N)] on the field,
+// which prints the first N items followed by a marker counting the rest. It
+// works on fields borrowing a collection too, and maps keep their `{k: v}`
+// shape, with the marker as their last entry. A
+// container level #[debug(non_exhaustive)] ends the output with `..` the way
+// Formatter's finish_non_exhaustive does.
+
+use derive_debug::CustomDebug;
+use std::collections::BTreeMap;
+
+#[derive(CustomDebug)]
+pub struct Samples {
+    #[debug(limit = 3)]
+    values: Vec<u32>,
+    #[debug(limit = 4)]
+    short: Vec<u32>,
+}
+
+#[derive(CustomDebug)]
+pub struct Borrowed<'a> {
+    #[debug(limit = 2)]
+    bytes: &'a [u8],
+    #[debug(limit = 2)]
+    names: BTreeMap<u8, &'a str>,
+    #[debug(limit = 1)]
+    index: &'a BTreeMap<u8, u8>,
+}
+
+#[derive(CustomDebug)]
+#[debug(non_exhaustive)]
+pub struct Config {
+    name: &'static str,
+}
+
+#[derive(CustomDebug)]
+#[debug(non_exhaustive)]
+pub enum State {
+    Idle,
+    Running(u8),
+    Done { code: i32 },
+}
+
+fn main() {
+    let samples = Samples {
+        values: (0..100_000).collect(),
+        short: vec![1, 2],
+    };
+    assert_eq!(format!("{:?}", samples), "Samples { values: [0, 1, 2, ... (99997 more)], short: [1, 2] }");
+
+    let index = BTreeMap::from([(1, 2)]);
+    let borrowed = Borrowed {
+        bytes: &[1, 2, 3, 4],
+        names: BTreeMap::from([(0, "a"), (1, "b"), (2, "c")]),
+        index: &index,
+    };
+    assert_eq!(format!("{:?}", borrowed), r#"Borrowed { bytes: [1, 2, ... (2 more)], names: {0: "a", 1: "b", ...: (1 more)}, index: {1: 2} }"#);
+
+    assert_eq!(format!("{:?}", Config { name: "c" }), r#"Config { name: "c", .. }"#);
+    assert_eq!(format!("{:?}", State::Idle), "Idle");
+    assert_eq!(format!("{:?}", State::Running(1)), "Running(1, ..)");
+    assert_eq!(format!("{:?}", State::Done { code: 0 }), "Done { code: 0, .. }");
+}
@@ -0,0 +1,58 @@
+// Raw identifiers print without their r# prefix and non-ASCII names print as
+// written, the same as with #[derive(Debug)]. A container level
+// #[debug(rename_all = "...")] prints every field name converted to another
+// case; a field's own rename still takes precedence.
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+pub struct r#Token {
+    r#type: &'static str,
+    r#match: bool,
+    größe: u8,
+}
+
+#[derive(Debug)]
+pub struct Expected {
+    r#type: &'static str,
+    r#match: bool,
+    größe: u8,
+}
+
+#[derive(CustomDebug)]
+#[allow(non_camel_case_types)]
+pub enum r#Kind {
+    r#struct { r#fn: u8 },
+    #[debug(skip)]
+    r#loop(u8),
+}
+
+#[derive(CustomDebug)]
+#[debug(rename_all = "camelCase")]
+pub struct Request {
+    user_id: u32,
+    retry_count_max: u8,
+    #[debug(rename = "X-Trace")]
+    trace_id: u64,
+}
+
+#[derive(CustomDebug)]
+#[debug(rename_all = "SCREAMING-KEBAB-CASE")]
+pub enum Header {
+    Content { content_length: usize },
+}
+
+fn main() {
+    let token = r#Token { r#type: "ident", r#match: true, größe: 3 };
+    let expected = Expected { r#type: "ident", r#match: true, größe: 3 };
+    assert_eq!(format!("{:?}", token), format!("{:?}", expected).replace("Expected", "Token"));
+
+    assert_eq!(format!("{:?}", r#Kind::r#struct { r#fn: 1 }), "struct { fn: 1 }");
+    assert_eq!(format!("{:?}", r#Kind::r#loop(1)), "loop(..)");
+
+    let request = Request { user_id: 1, retry_count_max: 2, trace_id: 3 };
+    assert_eq!(format!("{:?}", request), "Request { userId: 1, retryCountMax: 2, X-Trace: 3 }");
+
+    let header = Header::Content { content_length: 10 };
+    assert_eq!(format!("{:?}", header), "Content { CONTENT-LENGTH: 10 }");
+}
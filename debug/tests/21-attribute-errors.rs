@@ -0,0 +1,45 @@
+// Every #[debug ...] attribute on the container, its variants and all of their
+// fields is checked, also under a container #[debug(with = "...")], and all
+// the mistakes are reported together, each pointing at the offending option.
+// Doc comments and other macros' attributes are ignored. A compile_fail test.
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+#[debug(bound = "T: Debug", rename_all = "Title Case")]
+pub struct Struct<T> {
+    /// Documented, and formatted with a list-style attribute.
+    #[debug(fmt = "{:?}")]
+    #[allow(dead_code)]
+    ok: T,
+    #[debug(skip)]
+    #[debug(colour = "red")]
+    second_attribute: u8,
+    #[debug = 8]
+    not_a_string: u8,
+    #[debug(fmt = "{}", with = "fmt_value")]
+    conflict: u8,
+}
+
+#[derive(CustomDebug)]
+#[debug(phantom("Tagged"))]
+pub enum Enum {
+    #[debug(hidden)]
+    Variant(#[debug(rename = "zero")] u8, #[debug(redact(all))] u8),
+    #[debug]
+    Unit,
+}
+
+#[derive(CustomDebug)]
+#[debug(with = "opaque")]
+pub struct Opaque {
+    #[debug(colour = "red")]
+    #[debug = 8]
+    a: u8,
+}
+
+fn opaque(_: &Opaque, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str("Opaque")
+}
+
+fn main() {}
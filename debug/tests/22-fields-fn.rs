@@ -0,0 +1,82 @@
+// With #[debug(fields_fn)] on the container, the derive also generates a
+// `debug_fields` method listing the same fields the Debug impl prints as pairs
+// of their label and value, for structured logging. Skipped fields are left
+// out, and renames and field formats apply just like they do in the Debug
+// output. Tuple fields are labelled with their index. The values are plain
+// `&dyn Debug` references, writing the field the way Debug writes it.
+//
+// The method has bounds of its own: with `with` on the container, Debug needs
+// nothing from the fields even though debug_fields does.
+
+use derive_debug::CustomDebug;
+use std::fmt::{self, Debug};
+
+fn hex(value: &u32, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:#x}", value)
+}
+
+#[derive(CustomDebug)]
+#[debug(fields_fn)]
+pub struct Request<T> {
+    #[debug(rename = "request_id")]
+    id: u64,
+    #[debug(skip)]
+    _internal: u8,
+    #[debug = "{}ms"]
+    elapsed: u32,
+    #[debug(redact)]
+    token: String,
+    #[debug(with = "hex")]
+    flags: u32,
+    body: T,
+}
+
+#[derive(CustomDebug)]
+#[debug(fields_fn)]
+enum Event {
+    Start,
+    Move(i32, #[debug(skip)] i32, i32),
+    #[debug(skip)]
+    Secret { key: &'static str },
+}
+
+struct NotDebug;
+
+fn opaque<T>(_: &Handle<T>, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("Handle")
+}
+
+#[derive(CustomDebug)]
+#[debug(fields_fn, with = "opaque")]
+pub struct Handle<T> {
+    inner: T,
+}
+
+fn pairs<'a, I: Iterator<Item = (&'static str, &'a dyn Debug)>>(fields: I) -> Vec<String> {
+    fields.map(|(label, value)| format!("{}={:?}", label, value)).collect()
+}
+
+fn main() {
+    let request = Request {
+        id: 7,
+        _internal: 0,
+        elapsed: 12,
+        token: "hunter2".to_owned(),
+        flags: 255,
+        body: vec!["a"],
+    };
+    assert_eq!(
+        pairs(request.debug_fields()),
+        ["request_id=7", "elapsed=12ms", "token=<redacted>", "flags=0xff", r#"body=["a"]"#],
+    );
+
+    assert_eq!(format!("{:?}", Handle { inner: NotDebug }), "Handle");
+    assert_eq!(pairs(Handle { inner: 1 }.debug_fields()), ["inner=1"]);
+
+    let mut fields = request.debug_fields();
+    assert_eq!(fields.next().map(|(label, _)| label), Some("request_id"));
+
+    assert!(pairs(Event::Start.debug_fields()).is_empty());
+    assert_eq!(pairs(Event::Move(1, 2, 3).debug_fields()), ["0=1", "2=3"]);
+    assert!(pairs(Event::Secret { key: "k" }.debug_fields()).is_empty());
+}
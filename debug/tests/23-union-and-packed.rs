@@ -0,0 +1,69 @@
+// The fields of a #[repr(packed)] struct may be unaligned, and borrowing them
+// is an error. Like the standard library's derive, the generated code copies
+// each field to a local before formatting it, so they need to be Copy.
+//
+// Which field of a union is set isn't known, so a union prints as `Name { .. }`
+// unless the container is given #[debug(with = "...")], a function formatting
+// the whole value. That works on structs and enums too, none of their fields
+// need to implement Debug then.
+
+use derive_debug::{CustomDebug, CustomDisplay};
+use std::fmt;
+
+#[derive(CustomDebug, CustomDisplay, Clone, Copy)]
+#[repr(packed)]
+#[display("{tag}:{value}")]
+#[debug(fields_fn)]
+pub struct Packed {
+    tag: u8,
+    #[debug = "{:#x}"]
+    value: u32,
+}
+
+#[derive(CustomDebug)]
+#[repr(C, packed(2))]
+pub struct PackedTuple<T: Copy>(u8, T, #[debug(skip)] u64);
+
+#[derive(CustomDebug)]
+pub union Raw {
+    int: u32,
+    float: f32,
+}
+
+fn bits_fmt(bits: &Bits, f: &mut fmt::Formatter) -> fmt::Result {
+    // every bit pattern is a valid u32
+    write!(f, "Bits({:#010x})", unsafe { bits.int })
+}
+
+#[derive(CustomDebug)]
+#[debug(with = "bits_fmt")]
+pub union Bits {
+    int: u32,
+    float: f32,
+}
+
+fn opaque_fmt<T>(_: &Opaque<T>, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("Opaque")
+}
+
+pub struct NotDebug;
+
+#[derive(CustomDebug)]
+#[debug(with = "opaque_fmt")]
+pub struct Opaque<T> {
+    value: T,
+}
+
+fn main() {
+    let packed = Packed { tag: 1, value: 255 };
+    assert_eq!(format!("{:?}", packed), "Packed { tag: 1, value: 0xff }");
+    assert_eq!(format!("{}", packed), "1:255");
+    let fields: Vec<_> = packed.debug_fields().map(|(label, value)| format!("{}={:?}", label, value)).collect();
+    assert_eq!(fields, ["tag=1", "value=0xff"]);
+
+    assert_eq!(format!("{:?}", PackedTuple(1, 2i16, 3)), "PackedTuple(1, 2)");
+
+    assert_eq!(format!("{:?}", Raw { float: 1.0 }), "Raw { .. }");
+    assert_eq!(format!("{:?}", Bits { float: 1.0 }), "Bits(0x3f800000)");
+    assert_eq!(format!("{:?}", Opaque { value: NotDebug }), "Opaque");
+}
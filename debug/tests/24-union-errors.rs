@@ -0,0 +1,14 @@
+// A union's fields can't be read safely, so field attributes on a union and
+// #[debug(fields_fn)] are errors. This is a compile_fail test.
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+#[debug(fields_fn)]
+pub union Bits {
+    #[debug = "{:#x}"]
+    int: u32,
+    float: f32,
+}
+
+fn main() {}
@@ -0,0 +1,64 @@
+// Fields can be left out depending on their value. #[debug(skip_if = "...")]
+// names a function taking a reference to the field and returning true when it
+// should be left out, like Option::is_none or Vec::is_empty. On the container,
+// #[debug(skip_defaults)] leaves out fields holding their default value, for the
+// types whose default it knows: None, empty strings, slices and collections,
+// false and zero. Other fields, like ones of a type parameter, are always
+// printed, and the impl gets no bounds beyond Debug from it.
+//
+// The same fields are left out of the list given by #[debug(fields_fn)].
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+#[debug(fields_fn)]
+pub struct Entry {
+    id: u32,
+    #[debug(skip_if = "Option::is_none")]
+    parent: Option<u32>,
+    #[debug(skip_if = "Vec::is_empty")]
+    tags: Vec<&'static str>,
+}
+
+#[derive(CustomDebug)]
+#[debug(skip_defaults)]
+pub struct Settings<T> {
+    name: String,
+    level: T,
+    verbose: bool,
+    path: Option<&'static str>,
+    #[debug(skip_if = "is_odd")]
+    retries: u8,
+}
+
+#[derive(Debug)]
+pub struct NoDefault;
+
+fn is_odd(n: &u8) -> bool {
+    n % 2 == 1
+}
+
+#[derive(CustomDebug)]
+#[debug(skip_defaults)]
+pub enum Message {
+    Text(String, #[debug(skip_if = "str::is_empty")] &'static str),
+}
+
+fn main() {
+    let entry = Entry { id: 1, parent: None, tags: vec![] };
+    assert_eq!(format!("{:?}", entry), "Entry { id: 1 }");
+    let labels: Vec<_> = entry.debug_fields().map(|(label, _)| label).collect();
+    assert_eq!(labels, ["id"]);
+
+    let entry = Entry { id: 2, parent: Some(1), tags: vec!["a"] };
+    assert_eq!(format!("{:?}", entry), r#"Entry { id: 2, parent: Some(1), tags: ["a"] }"#);
+    assert_eq!(entry.debug_fields().count(), 3);
+
+    let settings = Settings { name: String::new(), level: 0.0, verbose: false, path: None, retries: 0 };
+    assert_eq!(format!("{:?}", settings), "Settings { level: 0.0, retries: 0 }");
+    let settings = Settings { name: "x".to_owned(), level: NoDefault, verbose: true, path: Some("/"), retries: 3 };
+    assert_eq!(format!("{:?}", settings), r#"Settings { name: "x", level: NoDefault, verbose: true, path: Some("/") }"#);
+
+    assert_eq!(format!("{:?}", Message::Text(String::new(), "")), "Text");
+    assert_eq!(format!("{:?}", Message::Text("hi".to_owned(), "re")), r#"Text("hi", "re")"#);
+}
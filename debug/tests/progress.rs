@@ -7,6 +7,23 @@ fn tests() {
     t.pass("tests/04-type-parameter.rs");
     t.pass("tests/05-phantom-data.rs");
     t.pass("tests/06-bound-trouble.rs");
-    //t.pass("tests/07-associated-type.rs");
-    //t.pass("tests/08-escape-hatch.rs");
+    t.pass("tests/07-associated-type.rs");
+    t.pass("tests/08-escape-hatch.rs");
+    t.pass("tests/09-enum.rs");
+    t.pass("tests/10-tuple-and-unit-struct.rs");
+    t.pass("tests/11-skip-redact-rename.rs");
+    t.pass("tests/12-with.rs");
+    t.pass("tests/13-bound.rs");
+    t.pass("tests/14-generics.rs");
+    t.pass("tests/15-associated-type-depth.rs");
+    t.pass("tests/16-phantom-containers.rs");
+    t.pass("tests/17-alternate-format.rs");
+    t.pass("tests/18-display.rs");
+    t.pass("tests/19-limit-non-exhaustive.rs");
+    t.pass("tests/20-raw-identifiers.rs");
+    t.compile_fail("tests/21-attribute-errors.rs");
+    t.pass("tests/22-fields-fn.rs");
+    t.pass("tests/23-union-and-packed.rs");
+    t.compile_fail("tests/24-union-errors.rs");
+    t.pass("tests/25-skip-if.rs");
 }
@@ -1,18 +1,216 @@
-use proc_macro::{Delimiter, Group, Literal, TokenStream, TokenTree};
+use proc_macro::{Delimiter, Group, Literal, Span, TokenStream, TokenTree};
 use proc_macro::token_stream::IntoIter;
-use quote::ToTokens;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
 use syn::{self, Ident, LitInt, Token};
 
+// The loop variables, the body is repeated for every combination of their values.
 struct Header {
+    vars: Vec<LoopVar>,
+    // the `#[trace]` attribute, if the expansion is to be written to a file
+    trace: Option<Span>,
+}
+
+// A loop variable, like `N in 0..4`.
+struct LoopVar {
     name: Ident,
-    start: i32,
-    end: i32,
+    start: i128,
+    end: i128,
+    // `a..=b` rather than `a..b`
+    inclusive: bool,
+    // the methods called on a parenthesized range, like `(0..64).step_by(4)`
+    adapters: Vec<Adapter>,
+    // how the values are written, taken from the bounds
+    format: LitFormat,
+}
+
+enum Adapter {
+    StepBy(usize),
+    Rev,
+}
+
+// A bound of the range, along with the literal it was written as.
+struct Bound {
+    value: i128,
+    lit: LitInt,
+}
+
+// The way a range bound is written, which the substituted literals follow: `0x00u8..0x10u8`
+// gives `0x00u8`, `0x01u8` and so on, and pastes `Reg#N` as `Reg00`, `Reg01`...
+struct LitFormat {
+    suffix: String,
+    radix: u32,
+    // the number of digits to pad values to with zeros, set by writing a bound like `00`
+    width: usize,
+}
+
+impl LitFormat {
+    fn from_bounds(start: &LitInt, end: &LitInt) -> Result<LitFormat, syn::Error> {
+        if !start.suffix().is_empty() && !end.suffix().is_empty() && start.suffix() != end.suffix() {
+            return Err(syn::Error::new(end.span(), format!("expected the same suffix as the start, `{}`", start.suffix())));
+        }
+        let suffix = if start.suffix().is_empty() { end.suffix() } else { start.suffix() };
+        let (radix, _) = radix_digits(start);
+        let width = [start, end].iter()
+            .map(|lit| radix_digits(lit).1)
+            .filter(|digits| digits.len() > 1 && digits.starts_with('0'))
+            .map(|digits| digits.len())
+            .max()
+            .unwrap_or(0);
+        Ok(LitFormat {
+            suffix: suffix.to_owned(),
+            radix,
+            width,
+        })
+    }
+
+    // the digits of `value`, without sign or prefix
+    fn digits(&self, value: i128) -> String {
+        let abs = value.unsigned_abs();
+        let width = self.width;
+        match self.radix {
+            16 => format!("{:0width$x}", abs, width = width),
+            8 => format!("{:0width$o}", abs, width = width),
+            2 => format!("{:0width$b}", abs, width = width),
+            _ => format!("{:0width$}", abs, width = width),
+        }
+    }
+
+    // the literal substituted for the loop variable
+    fn literal(&self, value: i128) -> Literal {
+        let repr = format!("{}{}", self.unsuffixed(value), self.suffix);
+        repr.parse().expect("valid integer literal")
+    }
+
+    // the literal without its suffix, for `N#suffix` to give one of its own
+    fn unsuffixed(&self, value: i128) -> String {
+        let prefix = match self.radix {
+            16 => "0x",
+            8 => "0o",
+            2 => "0b",
+            _ => "",
+        };
+        let sign = if value < 0 { "-" } else { "" };
+        format!("{}{}{}", sign, prefix, self.digits(value))
+    }
+
+    // the text pasted into an identifier for the loop variable
+    fn paste(&self, value: i128) -> String {
+        let sign = if value < 0 { "-" } else { "" };
+        format!("{}{}", sign, self.digits(value))
+    }
 }
 
-#[derive(Debug)]
-enum Segment {
-    Normal(TokenStream),
-    Repeat(TokenStream),
+// the radix of an integer literal and its digits as written, without sign, prefix, suffix or `_`
+fn radix_digits(lit: &LitInt) -> (u32, String) {
+    let repr = lit.to_string();
+    let unsigned = repr.trim_start_matches('-');
+    let unsuffixed = &unsigned[..unsigned.len() - lit.suffix().len()];
+    let (radix, digits) = match unsuffixed.get(..2) {
+        Some("0x") => (16, &unsuffixed[2..]),
+        Some("0o") => (8, &unsuffixed[2..]),
+        Some("0b") => (2, &unsuffixed[2..]),
+        _ => (10, unsuffixed),
+    };
+    (radix, digits.replace('_', ""))
+}
+
+impl Header {
+    // every combination of the variables' values, the last variable changing fastest
+    fn combinations(&self) -> Vec<Vec<i128>> {
+        let mut combinations = vec![vec![]];
+        for var in self.vars.iter() {
+            let values = var.values();
+            combinations = combinations.into_iter().flat_map(|prefix| {
+                values.iter().map(move |value| {
+                    let mut combination = prefix.clone();
+                    combination.push(*value);
+                    combination
+                })
+            }).collect();
+        }
+        combinations
+    }
+}
+
+impl LoopVar {
+    // the values the loop variable takes, in order. They're worked out from the first one, the
+    // distance between them and how many there are, so a wide range stepped through only makes
+    // the values it keeps
+    fn values(&self) -> Vec<i128> {
+        let mut count: u128 = if self.end < self.start {
+            0
+        } else {
+            // the distance between two i128 always fits in a u128
+            let len = (self.end as u128).wrapping_sub(self.start as u128);
+            if self.inclusive { len.saturating_add(1) } else { len }
+        };
+        let mut first = self.start;
+        let mut step: u128 = 1;
+        let mut descending = false;
+        for adapter in self.adapters.iter() {
+            match adapter {
+                Adapter::StepBy(n) => {
+                    let n = *n as u128;
+                    count = count.div_ceil(n);
+                    step = step.saturating_mul(n);
+                }
+                Adapter::Rev => {
+                    if count > 0 {
+                        first = offset(first, step * (count - 1), descending);
+                        descending = !descending;
+                    }
+                }
+            }
+        }
+        (0..count).map(|i| offset(first, i * step, descending)).collect()
+    }
+}
+
+// `value` moved by `distance` up, or down when `descending`, when the result fits in an i128
+fn offset(value: i128, distance: u128, descending: bool) -> i128 {
+    if descending {
+        (value as u128).wrapping_sub(distance) as i128
+    } else {
+        (value as u128).wrapping_add(distance) as i128
+    }
+}
+
+// The tokens of the input, which can be peeked at like a Peekable, along with where the last one
+// read was, to point at when they run out.
+struct Tokens {
+    iter: IntoIter,
+    peeked: Option<Option<TokenTree>>,
+    // the group the tokens are in, until one of them is read
+    last: Span,
+}
+
+impl Tokens {
+    fn new(stream: TokenStream, start: Span) -> Tokens {
+        Tokens { iter: stream.into_iter(), peeked: None, last: start }
+    }
+
+    fn peek(&mut self) -> Option<&TokenTree> {
+        let iter = &mut self.iter;
+        self.peeked.get_or_insert_with(|| iter.next()).as_ref()
+    }
+}
+
+impl Iterator for Tokens {
+    type Item = TokenTree;
+
+    fn next(&mut self) -> Option<TokenTree> {
+        let tt = match self.peeked.take() {
+            Some(tt) => tt,
+            None => self.iter.next(),
+        };
+        if let Some(tt) = &tt {
+            self.last = tt.span();
+        }
+        tt
+    }
 }
 
 #[proc_macro]
@@ -25,237 +223,612 @@ pub fn seq(input: TokenStream) -> TokenStream {
 
 fn process(input: TokenStream) -> Result<TokenStream, syn::Error> {
     let mut result = TokenStream::new();
-    let mut iter = input.clone().into_iter();
+    let invocation = input.to_string();
+    let mut iter = Tokens::new(input, Span::call_site());
     let header = parse_header(&mut iter)?;
     let body = braced_body(&mut iter)?;
-    let mut segments = vec![];
-    if find_repeat_section(body.clone().into_iter(), &mut segments) {
-        for seg in segments {
-            match seg {
-                Segment::Normal(n) => result.extend(n),
-                Segment::Repeat(r) => {
-                    for lit in header.start .. header.end {
-                        interrupt_ident_to_literal(&header.name, lit, r.clone().into_iter(), &mut result);
-                    }
-                }
-            }
-        }
-        eprintln!("{}", result);
+    if let Some(tt) = iter.next() {
+        return Err(syn::Error::new(tt.span().into(), "unexpected token after the loop body"));
+    }
+    let combinations: Vec<Vec<(&LoopVar, i128)>> = header.combinations().into_iter()
+        .map(|values| header.vars.iter().zip(values).collect())
+        .collect();
+    if has_repeat_section(&body) {
+        expand_repeat_sections(&combinations, body.into_iter(), &mut result)?;
     } else {
-        for lit in header.start .. header.end {
-            interrupt_ident_to_literal(&header.name, lit, body.clone().into_iter(), &mut result);
+        for vars in combinations.iter() {
+            interrupt_ident_to_literal(vars, body.clone().into_iter(), &mut result, &[])?;
         }
     }
+    if let Some(span) = header.trace {
+        write_trace(&header, &invocation, &result).map_err(|e| syn::Error::new(span.into(), e))?;
+    }
     Ok(result)
 }
 
-fn parse_header(iter: &mut IntoIter) -> Result<Header, syn::Error> {
+// parses the loop variables, separated by commas
+fn parse_header(iter: &mut Tokens) -> Result<Header, syn::Error> {
+    let trace = parse_trace_attr(iter)?;
+    let mut vars: Vec<LoopVar> = vec![parse_loop_var(iter)?];
+    while let Some(TokenTree::Punct(p)) = iter.peek() {
+        if p.as_char() != ',' {
+            break;
+        }
+        iter.next();
+        if let Some(TokenTree::Group(g)) = iter.peek() {
+            if g.delimiter() == Delimiter::Brace {
+                break;
+            }
+        }
+        let var = parse_loop_var(iter)?;
+        if vars.iter().any(|v| v.name == var.name) {
+            return Err(syn::Error::new(var.name.span(), format!("`{}` is already a loop variable", var.name)));
+        }
+        vars.push(var);
+    }
+    Ok(Header { vars, trace })
+}
+
+// parses a leading `#[trace]`, the only attribute the header takes
+fn parse_trace_attr(iter: &mut Tokens) -> Result<Option<Span>, syn::Error> {
+    match iter.peek() {
+        Some(TokenTree::Punct(p)) if p.as_char() == '#' => {}
+        _ => return Ok(None),
+    }
+    iter.next();
+    let group = match next_token(iter)? {
+        TokenTree::Group(g) if g.delimiter() == Delimiter::Bracket => g,
+        tt => return Err(syn::Error::new(tt.span().into(), "expected `[trace]`")),
+    };
+    let attr: Vec<TokenTree> = group.stream().into_iter().collect();
+    match attr.as_slice() {
+        [TokenTree::Ident(ident)] if ident.to_string() == "trace" => Ok(Some(group.span())),
+        _ => Err(syn::Error::new(group.span().into(), "unknown attribute, expected `#[trace]`")),
+    }
+}
+
+fn parse_loop_var(iter: &mut Tokens) -> Result<LoopVar, syn::Error> {
     let name = parse_header_name(iter)?;
     parse_token::<Token![in]>(iter)?;
-    let start = parse_header_lit(iter)?;
-    parse_token::<Token![.]>(iter)?;
-    parse_token::<Token![.]>(iter)?;
-    let end = parse_header_lit(iter)?;
-    Ok(Header{
+    let (start, inclusive, end, adapters) = match iter.peek() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis && is_range(&g.stream()) => {
+            let g = g.clone();
+            iter.next();
+            let mut inner = Tokens::new(g.stream(), g.span());
+            let (start, inclusive, end) = parse_range(&mut inner)?;
+            if let Some(tt) = inner.next() {
+                return Err(syn::Error::new(tt.span().into(), "unexpected token after the range"));
+            }
+            (start, inclusive, end, parse_adapters(iter)?)
+        }
+        _ => {
+            let (start, inclusive, end) = parse_range(iter)?;
+            (start, inclusive, end, vec![])
+        }
+    };
+    Ok(LoopVar{
         name,
-        start,
-        end,
+        format: LitFormat::from_bounds(&start.lit, &end.lit)?,
+        start: start.value,
+        end: end.value,
+        inclusive,
+        adapters,
     })
 }
 
+// parses `a..b` or `a..=b`
+fn parse_range(iter: &mut Tokens) -> Result<(Bound, bool, Bound), syn::Error> {
+    let start = parse_bound(iter, 0)?;
+    let inclusive = parse_range_op(iter)?;
+    let end = parse_bound(iter, 0)?;
+    Ok((start, inclusive, end))
+}
 
-fn parse_header_name(iter: &mut IntoIter) -> Result<Ident, syn::Error> {
-    if let Some(tt) = iter.next() {
-        let ts: TokenStream = tt.into();
-        let ident: Ident = syn::parse(ts)?;
-        return Ok(ident);
+// parses the method calls following a parenthesized range, `.step_by(n)` and `.rev()`
+fn parse_adapters(iter: &mut Tokens) -> Result<Vec<Adapter>, syn::Error> {
+    let mut adapters = vec![];
+    while let Some(TokenTree::Punct(p)) = iter.peek() {
+        if p.as_char() != '.' {
+            break;
+        }
+        iter.next();
+        let method = parse_header_name(iter)?;
+        let args = match next_token(iter)? {
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Parenthesis => g,
+            tt => return Err(syn::Error::new(tt.span().into(), "expected `(`")),
+        };
+        let mut args_iter = Tokens::new(args.stream(), args.span());
+        let adapter = if method == "step_by" {
+            let ts: TokenStream = next_token(&mut args_iter)
+                .map_err(|_| syn::Error::new(args.span().into(), "expected the step"))?
+                .into();
+            let lit: LitInt = syn::parse(ts)?;
+            let step = lit.base10_parse::<usize>()?;
+            if step == 0 {
+                return Err(syn::Error::new(lit.span(), "the step must be greater than zero"));
+            }
+            Adapter::StepBy(step)
+        } else if method == "rev" {
+            Adapter::Rev
+        } else {
+            return Err(syn::Error::new(method.span(), "expected `step_by` or `rev`"));
+        };
+        if let Some(tt) = args_iter.next() {
+            return Err(syn::Error::new(tt.span().into(), "unexpected argument"));
+        }
+        adapters.push(adapter);
     }
-    Err(syn::Error::new(proc_macro::Span::call_site().into(), "unexpected eof"))
+    Ok(adapters)
 }
 
-fn parse_token<T: syn::parse::Parse>(iter: &mut IntoIter) -> Result<(), syn::Error> {
-    if let Some(tt) = iter.next() {
-        let ts: TokenStream = tt.into();
-        let _: T = syn::parse(ts)?;
-        return Ok(());
-    }
-    Err(syn::Error::new(proc_macro::Span::call_site().into(), "unexpected eof"))
+// the next token, or an error at the last one read when the input ends
+fn next_token(iter: &mut Tokens) -> Result<TokenTree, syn::Error> {
+    let last = iter.last;
+    iter.next().ok_or_else(|| syn::Error::new(last.into(), "unexpected eof"))
+}
 
+fn parse_header_name(iter: &mut Tokens) -> Result<Ident, syn::Error> {
+    let ts: TokenStream = next_token(iter)?.into();
+    syn::parse(ts)
 }
 
-fn parse_header_lit(iter: &mut IntoIter) -> Result<i32, syn::Error> {
-    if let Some(tt) = iter.next() {
-        let ts: TokenStream = tt.into();
-        let lit: LitInt = syn::parse(ts)?;
-        let v = lit.base10_parse::<i32>()?;
-        return Ok(v);
+fn parse_token<T: syn::parse::Parse>(iter: &mut Tokens) -> Result<(), syn::Error> {
+    let ts: TokenStream = next_token(iter)?.into();
+    let _: T = syn::parse(ts)?;
+    Ok(())
+}
+
+// parses `..` or `..=`, returns true for the inclusive one
+fn parse_range_op(iter: &mut Tokens) -> Result<bool, syn::Error> {
+    for _ in 0..2 {
+        match next_token(iter)? {
+            TokenTree::Punct(p) if p.as_char() == '.' => {}
+            tt => return Err(syn::Error::new(tt.span().into(), "expected `..` or `..=`")),
+        }
+    }
+    match iter.peek() {
+        Some(TokenTree::Punct(p)) if p.as_char() == '=' => {
+            iter.next();
+            Ok(true)
+        }
+        _ => Ok(false),
     }
-    Err(syn::Error::new(proc_macro::Span::call_site().into(), "unexpected eof"))
 }
 
-fn braced_body(iter: &mut IntoIter) -> Result<TokenStream, syn::Error> {
-    if let Some(tt) = iter.next() {
-        if let proc_macro::TokenTree::Group(g) = tt {
-            match g.delimiter() {
-                proc_macro::Delimiter::Brace => return Ok(g.stream()),
-                _ => {
-                    let err = syn::Error::new(g.span().into(), "unexpected delimiter");
-                    return Err(err);
-                }
+// parses a bound: an integer literal, or arithmetic on integer literals like `-4`, `1 << 3` or
+// `(16 * 2)`, taking operators with the same precedence as Rust does
+fn parse_bound(iter: &mut Tokens, min_prec: u8) -> Result<Bound, syn::Error> {
+    let mut lhs = parse_bound_operand(iter)?;
+    while let Some((op, prec)) = peek_binop(iter) {
+        if prec < min_prec {
+            break;
+        }
+        let span = next_token(iter)?.span();
+        if op == '<' || op == '>' {
+            // `<<` and `>>` come as two tokens
+            match next_token(iter)? {
+                TokenTree::Punct(p) if p.as_char() == op => {}
+                tt => return Err(syn::Error::new(tt.span().into(), format!("expected `{}{}`", op, op))),
             }
         }
-        return Err(syn::Error::new(tt.span().into(), "a group is expected"));
+        let rhs = parse_bound(iter, prec + 1)?;
+        let (a, b) = (lhs.value, rhs.value);
+        let value = match op {
+            '*' => a.checked_mul(b),
+            '/' => a.checked_div(b),
+            '%' => a.checked_rem(b),
+            '+' => a.checked_add(b),
+            '-' => a.checked_sub(b),
+            '<' => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+            '>' => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+            '&' => Some(a & b),
+            '^' => Some(a ^ b),
+            _ => Some(a | b),
+        };
+        lhs.value = value.ok_or_else(|| syn::Error::new(span.into(), "the arithmetic overflows or divides by zero"))?;
     }
-    return Err(syn::Error::new(proc_macro::Span::call_site().into(), "unexpected eof"));
+    Ok(lhs)
 }
 
-// interrupt N to lit
-fn interrupt_ident_to_literal(name: &Ident, lit: i32, iter: IntoIter, output: &mut TokenStream) {
-    let mut sharp = None;
-    let mut ident_before_sharp: Option<TokenTree> = None;
-    for tt in iter {
-        match &tt {
-            TokenTree::Group(g) => {
-                if let Some(ident0) = &ident_before_sharp {
-                    output.extend::<TokenStream>(ident0.clone().into());
-                    ident_before_sharp = None;
-                }
+// the binary operator coming next and its precedence, `<` and `>` standing for `<<` and `>>`
+fn peek_binop(iter: &mut Tokens) -> Option<(char, u8)> {
+    match iter.peek() {
+        Some(TokenTree::Punct(p)) => match p.as_char() {
+            '*' | '/' | '%' => Some((p.as_char(), 5)),
+            '+' | '-' => Some((p.as_char(), 4)),
+            '<' | '>' => Some((p.as_char(), 3)),
+            '&' => Some(('&', 2)),
+            '^' => Some(('^', 1)),
+            '|' => Some(('|', 0)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
 
-                let mut tmp = TokenStream::new();
-                interrupt_ident_to_literal(name, lit, g.stream().into_iter(), &mut tmp);
-                let mut new_g = Group::new(g.delimiter(), tmp);
-                new_g.set_span(g.span());
-                let new_tt: TokenTree = new_g.into();
-                output.extend::<TokenStream>(new_tt.into());
+// parses a literal, a negated operand or a parenthesized bound, the written literal of the bound
+// is the first one in it
+fn parse_bound_operand(iter: &mut Tokens) -> Result<Bound, syn::Error> {
+    match next_token(iter)? {
+        TokenTree::Punct(p) if p.as_char() == '-' => {
+            if let Some(TokenTree::Literal(_)) = iter.peek() {
+                let ts: TokenStream = next_token(iter)?.into();
+                let lit: LitInt = syn::parse(ts)?;
+                // parsed along with the sign so that the smallest value fits
+                let value = format!("-{}", lit.base10_digits()).parse::<i128>().map_err(|err| syn::Error::new(lit.span(), err))?;
+                let lit = LitInt::new(&format!("-{}", lit), lit.span());
+                return Ok(Bound { value, lit });
             }
-            TokenTree::Punct(p) => {
-                // 如果检测到 ident_before_sharp#name 则合并 ident_before_sharp 和 name 追加到结果；
-                // 否则需要将 ident_before_sharp 作为单独的 ident 追加到结果。
-                if p.as_char() == '#' {
-                    sharp = Some(tt.clone());
-                    continue
-                }
+            let mut bound = parse_bound_operand(iter)?;
+            bound.value = bound.value.checked_neg().ok_or_else(|| syn::Error::new(p.span().into(), "the arithmetic overflows"))?;
+            Ok(bound)
+        }
+        tt @ TokenTree::Literal(_) => {
+            let lit: LitInt = syn::parse(tt.into())?;
+            Ok(Bound { value: lit.base10_parse::<i128>()?, lit })
+        }
+        // `None` groups come from `macro_rules!` fragments like `$n:literal` or `$n:expr`
+        TokenTree::Group(g) if g.delimiter() == Delimiter::Parenthesis || g.delimiter() == Delimiter::None => {
+            let mut inner = Tokens::new(g.stream(), g.span());
+            if inner.peek().is_none() {
+                return Err(syn::Error::new(g.span().into(), "expected a bound"));
+            }
+            let bound = parse_bound(&mut inner, 0)?;
+            if let Some(tt) = inner.next() {
+                return Err(syn::Error::new(tt.span().into(), "unexpected token in the bound"));
+            }
+            Ok(bound)
+        }
+        tt => Err(syn::Error::new(tt.span().into(), "expected an integer literal, or arithmetic on integer literals")),
+    }
+}
+
+// returns true if the stream is a range like `0..8`, rather than a parenthesized bound
+fn is_range(stream: &TokenStream) -> bool {
+    stream.clone().into_iter().any(|tt| match tt {
+        TokenTree::Punct(p) => p.as_char() == '.',
+        _ => false,
+    })
+}
 
+fn braced_body(iter: &mut Tokens) -> Result<TokenStream, syn::Error> {
+    match next_token(iter)? {
+        TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => Ok(g.stream()),
+        TokenTree::Group(g) => Err(syn::Error::new(g.span().into(), "unexpected delimiter")),
+        tt => Err(syn::Error::new(tt.span().into(), "a group is expected")),
+    }
+}
 
-                if let Some(ident0) = &ident_before_sharp {
-                    output.extend::<TokenStream>(ident0.clone().into());
-                    ident_before_sharp = None;
+// interrupt the loop variables to their values: `N` becomes a literal, `prefix#N` and
+// `prefix#N#suffix` an identifier (or a lifetime, after a `'`), `N#suffix` a literal with
+// that suffix, like `N#u8`, and `~(N * 8)` the literal the arithmetic in it gives. `inner`
+// has the variables of the nested seq! invocations the tokens are in, which are left for them.
+fn interrupt_ident_to_literal(vars: &[(&LoopVar, i128)], iter: IntoIter, output: &mut TokenStream, inner: &[String]) -> Result<(), syn::Error> {
+    let tokens: Vec<TokenTree> = iter.collect();
+    let mut result: Vec<TokenTree> = vec![];
+    // whether the last identifier in `result` ends with a pasted value, which can take a
+    // `#suffix` after it
+    let mut pasted = false;
+    let mut i = 0;
+    while i < tokens.len() {
+        let tt = &tokens[i];
+        match tt {
+            TokenTree::Punct(p) if p.as_char() == '#' => {
+                if let (Some(TokenTree::Ident(prev)), Some(TokenTree::Ident(next))) = (result.last(), tokens.get(i + 1)) {
+                    let piece = match find_var(vars, next) {
+                        Some((var, value)) => Some((var.format.paste(*value), true)),
+                        // a value takes a single suffix, so in `prefix#R#_#C` a nested seq! still sees `#C`,
+                        // and its variables aren't suffixes, so it sees the `#C` of `prefix#R#C` too
+                        None if pasted && !inner.contains(&next.to_string()) => Some((next.to_string(), false)),
+                        None => None,
+                    };
+                    if let Some((piece, is_value)) = piece {
+                        let ident = pasted_ident(&format!("{}{}", prev, piece), prev.span(), p.span())?;
+                        *result.last_mut().unwrap() = ident.into();
+                        pasted = is_value;
+                        i += 2;
+                        continue;
+                    }
                 }
-                // append the sharp if has any
-                if let Some(s) = &sharp {
-                    output.extend::<TokenStream>(s.clone().into());
-                    sharp = None;
+                result.push(tt.clone());
+            }
+            TokenTree::Punct(p) if p.as_char() == '~' && is_parenthesized(tokens.get(i + 1)) => {
+                let group = match &tokens[i + 1] {
+                    TokenTree::Group(g) => g,
+                    _ => unreachable!(),
+                };
+                let mut expr = TokenStream::new();
+                interrupt_ident_to_literal(vars, group.stream().into_iter(), &mut expr, inner)?;
+                if !inner.is_empty() && has_ident(&expr) {
+                    // left for the nested seq! to work out
+                    let mut new_g = Group::new(Delimiter::Parenthesis, expr);
+                    new_g.set_span(group.span());
+                    result.extend(vec![tt.clone(), new_g.into()]);
+                } else {
+                    result.push(value_token(evaluate(expr, group.span())?));
                 }
-                output.extend::<TokenStream>(tt.into());
+                pasted = false;
+                i += 2;
+                continue;
             }
-            TokenTree::Ident(i) => {
-                if i.to_string() == name.to_string() {
-                    let t: TokenTree = Literal::i32_unsuffixed(lit).into();
-                    let mut ts: TokenStream = t.into();
-                    if let Some(_) = sharp {
-                        if let Some(ident0) = &ident_before_sharp {
-                            let new_name = format!("{}{}", ident0, lit);
-                            let new_ident = syn::Ident::new(&&new_name, ident0.span().into());
-                            ts = new_ident.to_token_stream().into();
-                            ident_before_sharp = None; // avoid duplicated extend to result
-                        }
-                        sharp = None;
+            TokenTree::Ident(ident) if find_var(vars, ident).is_some() => {
+                let (var, value) = find_var(vars, ident).unwrap();
+                if let (Some(TokenTree::Punct(p)), Some(TokenTree::Ident(next))) = (tokens.get(i + 1), tokens.get(i + 2)) {
+                    if p.as_char() == '#' {
+                        let piece = match find_var(vars, next) {
+                            Some((var, value)) => var.format.paste(*value),
+                            None => next.to_string(),
+                        };
+                        let repr = format!("{}{}", var.format.unsuffixed(*value), piece);
+                        let mut lit: Literal = repr.parse().map_err(|_| syn::Error::new(p.span().into(), format!("`{}` is not a valid literal", repr)))?;
+                        lit.set_span(ident.span());
+                        result.push(value_token(lit));
+                        pasted = false;
+                        i += 3;
+                        continue;
                     }
-                    output.extend(ts);
-                    continue
-                }
-
-                if let Some(ident0) = &ident_before_sharp {
-                    output.extend::<TokenStream>(ident0.clone().into());
                 }
-                ident_before_sharp = Some(tt.clone());
+                let mut lit = var.format.literal(*value);
+                lit.set_span(ident.span());
+                result.push(value_token(lit));
             }
-            _ => {
-                if let Some(ident0) = &ident_before_sharp {
-                    output.extend::<TokenStream>(ident0.clone().into());
-                    ident_before_sharp = None;
-                }
-                // append the sharp if has any
-                if let Some(s) = &sharp {
-                    output.extend::<TokenStream>(s.clone().into());
-                    sharp = None;
-                }
-                output.extend::<TokenStream>(tt.into());
+            TokenTree::Ident(ident) if ident.to_string() == "seq" && is_nested_seq(&tokens[i + 1..]) => {
+                // a nested seq! binding a variable of the same name leaves it to that one
+                let group = match &tokens[i + 2] {
+                    TokenTree::Group(g) => g,
+                    _ => unreachable!(),
+                };
+                let shadowed = nested_seq_vars(&group.stream());
+                let outer: Vec<(&LoopVar, i128)> = vars.iter().filter(|(var, _)| !shadowed.contains(&var.name.to_string())).cloned().collect();
+                let mut tmp = TokenStream::new();
+                let inner = [inner, &shadowed[..]].concat();
+                interrupt_ident_to_literal(&outer, group.stream().into_iter(), &mut tmp, &inner)?;
+                let mut new_g = Group::new(group.delimiter(), tmp);
+                new_g.set_span(group.span());
+                result.extend(vec![tt.clone(), tokens[i + 1].clone(), new_g.into()]);
+                pasted = false;
+                i += 3;
+                continue;
+            }
+            TokenTree::Group(g) => {
+                let mut tmp = TokenStream::new();
+                interrupt_ident_to_literal(vars, g.stream().into_iter(), &mut tmp, inner)?;
+                let mut new_g = Group::new(g.delimiter(), tmp);
+                new_g.set_span(g.span());
+                result.push(new_g.into());
             }
+            _ => result.push(tt.clone()),
         }
+        pasted = false;
+        i += 1;
+    }
+    output.extend(result);
+    Ok(())
+}
 
+// the token a substituted value is emitted as. A negative literal reads as a `-` before the
+// literal, so it's parenthesized, or `N.abs()` would be `-(2.abs())`. An invisible group
+// isn't enough, the parser looks through it there
+fn value_token(lit: Literal) -> TokenTree {
+    if !lit.to_string().starts_with('-') {
+        return lit.into();
     }
+    let span = lit.span();
+    let mut group = Group::new(Delimiter::Parenthesis, TokenTree::from(lit).into());
+    group.set_span(span);
+    group.into()
 }
 
-fn find_repeat_section(iter: IntoIter, segments: &mut Vec<Segment>) -> bool {
-    let mut found = false;
-    let mut sharp: Option<TokenTree> = None;
-    let mut repeat: Option<TokenStream> = None;
-    for tt in iter {
-        match &tt {
-            TokenTree::Group(g) => {
-                if sharp.is_some() && g.delimiter() == Delimiter::Parenthesis{
-                    repeat = Some(g.stream());
-                } else {
-                    if let Some(s) = &sharp {
-                        segments.push(Segment::Normal(s.clone().into()));
-                        sharp = None;
-                    }
-                    repeat = None;
-                    if find_repeat_section(g.stream().into_iter(), segments) {
-                        found = true;
-                        continue;
-                    }
-                    segments.push(Segment::Normal(tt.clone().into()));
-                }
+// works out the arithmetic of `~(...)` once the values are in, written like the first literal in it
+fn evaluate(expr: TokenStream, span: Span) -> Result<Literal, syn::Error> {
+    let mut iter = Tokens::new(expr, span);
+    if iter.peek().is_none() {
+        return Err(syn::Error::new(span.into(), "expected an expression"));
+    }
+    let bound = parse_bound(&mut iter, 0)?;
+    if let Some(tt) = iter.next() {
+        return Err(syn::Error::new(tt.span().into(), "unexpected token in the expression"));
+    }
+    let mut lit = LitFormat::from_bounds(&bound.lit, &bound.lit)?.literal(bound.value);
+    lit.set_span(span);
+    Ok(lit)
+}
+
+fn is_parenthesized(tt: Option<&TokenTree>) -> bool {
+    match tt {
+        Some(TokenTree::Group(g)) => g.delimiter() == Delimiter::Parenthesis,
+        _ => false,
+    }
+}
+
+fn has_ident(stream: &TokenStream) -> bool {
+    stream.clone().into_iter().any(|tt| match tt {
+        TokenTree::Ident(_) => true,
+        TokenTree::Group(g) => has_ident(&g.stream()),
+        _ => false,
+    })
+}
+
+// makes the identifier `prefix#N` pastes together, or an error at the `#` when that isn't one
+fn pasted_ident(text: &str, span: Span, sharp: Span) -> Result<proc_macro::Ident, syn::Error> {
+    let (raw, name) = match text.strip_prefix("r#") {
+        Some(name) => (true, name),
+        None => (false, text),
+    };
+    let valid = syn::parse_str::<Ident>(name).is_ok();
+    if !valid {
+        return Err(syn::Error::new(sharp.into(), format!("`{}` is not a valid identifier", text)));
+    }
+    if raw {
+        Ok(proc_macro::Ident::new_raw(name, span))
+    } else {
+        Ok(proc_macro::Ident::new(name, span))
+    }
+}
+
+fn find_var<'a>(vars: &'a [(&LoopVar, i128)], ident: &proc_macro::Ident) -> Option<&'a (&'a LoopVar, i128)> {
+    vars.iter().find(|(var, _)| var.name == ident.to_string())
+}
+
+// returns true if the tokens following a `seq` identifier are the rest of a seq! invocation
+fn is_nested_seq(rest: &[TokenTree]) -> bool {
+    match rest {
+        [TokenTree::Punct(p), TokenTree::Group(_), ..] => p.as_char() == '!',
+        _ => false,
+    }
+}
+
+// the names of the loop variables a nested seq! invocation binds
+fn nested_seq_vars(stream: &TokenStream) -> Vec<String> {
+    let tokens: Vec<TokenTree> = stream.clone().into_iter().collect();
+    tokens.windows(2).filter_map(|pair| match pair {
+        [TokenTree::Ident(name), TokenTree::Ident(kw)] if kw.to_string() == "in" => Some(name.to_string()),
+        _ => None,
+    }).collect()
+}
+
+// returns true if the body has a `#(...)*` section outside of a nested seq!, in which case
+// only those sections are repeated
+fn has_repeat_section(stream: &TokenStream) -> bool {
+    let tokens: Vec<TokenTree> = stream.clone().into_iter().collect();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            TokenTree::Ident(ident) if ident.to_string() == "seq" && is_nested_seq(&tokens[i + 1..]) => {
+                i += 3;
+                continue;
             }
-            TokenTree::Punct(p) => {
-                match p.as_char() {
-                    '#' => sharp = Some(tt.clone()),
-                    '*' => {
-                        if sharp.is_some() && repeat.is_some() {
-                            if let Some(r) = &repeat {
-                                segments.push(Segment::Repeat(r.clone().into()));
-                                found = true;
-                            }
-                            continue;
-                        }
+            TokenTree::Punct(p) if p.as_char() == '#' && repeat_section(&tokens[i + 1..]).is_some() => return true,
+            TokenTree::Group(g) if has_repeat_section(&g.stream()) => return true,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
 
-                        if let Some(s) = &sharp {
-                            segments.push(Segment::Normal(s.clone().into()));
-                        }
-                        if let Some(r) = &repeat {
-                            segments.push(Segment::Normal(r.clone().into()));
-                        }
-                        sharp = None;
-                        repeat = None;
-                        segments.push(Segment::Normal(tt.clone().into()));
-                    }
-                    _ => {
-                        if let Some(s) = &sharp {
-                            segments.push(Segment::Normal(s.clone().into()));
-                        }
-                        if let Some(r) = &repeat {
-                            segments.push(Segment::Normal(r.clone().into()));
+// the body and separator of the `#(...)*` or `#(...),*` section the tokens after a `#` make,
+// along with how many of them it takes up
+fn repeat_section(rest: &[TokenTree]) -> Option<(&Group, Option<&TokenTree>, usize)> {
+    let group = match rest.first() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => g,
+        _ => return None,
+    };
+    match (rest.get(1), rest.get(2)) {
+        (Some(TokenTree::Punct(star)), _) if star.as_char() == '*' => Some((group, None, 2)),
+        (Some(sep @ TokenTree::Punct(_)), Some(TokenTree::Punct(star))) if star.as_char() == '*' => Some((group, Some(sep), 3)),
+        _ => None,
+    }
+}
+
+// copies the body, repeating each `#(...)*` section in place for every combination of the
+// loop variables, with the separator between the copies
+fn expand_repeat_sections(combinations: &[Vec<(&LoopVar, i128)>], iter: IntoIter, output: &mut TokenStream) -> Result<(), syn::Error> {
+    let tokens: Vec<TokenTree> = iter.collect();
+    let mut i = 0;
+    while i < tokens.len() {
+        let tt = &tokens[i];
+        match tt {
+            TokenTree::Ident(ident) if ident.to_string() == "seq" && is_nested_seq(&tokens[i + 1..]) => {
+                // its repeat sections are its own
+                output.extend(tokens[i..i + 3].iter().cloned());
+                i += 3;
+                continue;
+            }
+            TokenTree::Punct(p) if p.as_char() == '#' => {
+                if let Some((group, sep, len)) = repeat_section(&tokens[i + 1..]) {
+                    for (n, vars) in combinations.iter().enumerate() {
+                        if let (Some(sep), true) = (sep, n > 0) {
+                            output.extend(Some(sep.clone()));
                         }
-                        sharp = None;
-                        repeat = None;
-                        segments.push(Segment::Normal(tt.clone().into()));
+                        interrupt_ident_to_literal(vars, group.stream().into_iter(), output, &[])?;
                     }
+                    i += len + 1;
+                    continue;
                 }
+                output.extend(Some(tt.clone()));
             }
-            _ => {
-                if let Some(s) = &sharp {
-                    segments.push(Segment::Normal(s.clone().into()));
-                }
-                if let Some(r) = &repeat {
-                    segments.push(Segment::Normal(r.clone().into()));
-                }
-                sharp = None;
-                repeat = None;
-                segments.push(Segment::Normal(tt.clone().into()))
+            TokenTree::Group(g) => {
+                let mut tmp = TokenStream::new();
+                expand_repeat_sections(combinations, g.stream().into_iter(), &mut tmp)?;
+                let mut new_g = Group::new(g.delimiter(), tmp);
+                new_g.set_span(g.span());
+                output.extend(Some(TokenTree::from(new_g)));
             }
+            _ => output.extend(Some(tt.clone())),
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+// writes the expansion to `seq-trace/` under OUT_DIR, or under the target directory for crates
+// without a build script, in a file named after the loop variables and the invocation. SEQ_TRACE_DIR
+// overrides the directory.
+fn write_trace(header: &Header, invocation: &str, expansion: &TokenStream) -> Result<(), String> {
+    let env = |name| std::env::var_os(name).map(PathBuf::from);
+    let dir = match env("SEQ_TRACE_DIR") {
+        Some(dir) => dir,
+        None => env("OUT_DIR")
+            .or_else(|| env("CARGO_TARGET_DIR"))
+            .or_else(|| env("CARGO_MANIFEST_DIR").map(|dir| dir.join("target")))
+            .ok_or("`#[trace]` needs OUT_DIR, CARGO_TARGET_DIR or CARGO_MANIFEST_DIR, or SEQ_TRACE_DIR set to the directory to write the trace to")?
+            .join("seq-trace"),
+    };
+    let names: Vec<String> = header.vars.iter().map(|var| var.name.to_string()).collect();
+    let mut hasher = DefaultHasher::new();
+    invocation.hash(&mut hasher);
+    let path = dir.join(format!("{}-{:016x}.rs", names.join("_"), hasher.finish()));
+    let mut text = String::new();
+    pretty_print(expansion.clone(), 0, &mut text);
+    std::fs::create_dir_all(&dir)
+        .and_then(|()| std::fs::write(&path, text))
+        .map_err(|e| format!("could not write the trace to `{}`: {}", path.display(), e))
+}
+
+// writes the tokens as the compiler prints them, breaking the line after each `;` and around
+// the contents of braces, which are indented
+fn pretty_print(stream: TokenStream, depth: usize, out: &mut String) {
+    let mut line = TokenStream::new();
+    // whether a block was just closed, its `}` still to be written
+    let mut closed = false;
+    for tt in stream {
+        // `};`, `},`, `}.method()` and `} else` stay together
+        let joined = matches!(&tt, TokenTree::Punct(p) if p.as_char() != '#')
+            || matches!(&tt, TokenTree::Ident(i) if i.to_string() == "else");
+        if closed && !joined {
+            push_line(out, depth, std::mem::take(&mut closed), "");
+        }
+        match tt {
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Brace && !g.stream().is_empty() => {
+                let head = std::mem::replace(&mut line, TokenStream::new()).to_string();
+                push_line(out, depth, closed, format!("{} {{", head).trim_start());
+                pretty_print(g.stream(), depth + 1, out);
+                closed = true;
+            }
+            TokenTree::Punct(p) if p.as_char() == ';' => {
+                line.extend(Some(TokenTree::from(p)));
+                let text = std::mem::replace(&mut line, TokenStream::new()).to_string();
+                push_line(out, depth, std::mem::take(&mut closed), &text);
+            }
+            tt => line.extend(Some(tt)),
+        }
+    }
+    let text = line.to_string();
+    if closed || !text.is_empty() {
+        push_line(out, depth, closed, &text);
+    }
+}
+
+// writes `text` on a line of its own, after the `}` of the block just closed if there's one
+fn push_line(out: &mut String, depth: usize, closed: bool, text: &str) {
+    out.push_str(&"    ".repeat(depth));
+    if closed {
+        out.push('}');
+        if !text.is_empty() && !text.starts_with([';', ',', '.', '?']) {
+            out.push(' ');
         }
     }
-    found
+    out.push_str(text);
+    out.push('\n');
 }
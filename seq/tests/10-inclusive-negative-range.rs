@@ -0,0 +1,39 @@
+// The range in the header can be inclusive, written `a..=b`, and either bound
+// can be a negative literal. A negative value stays one operand, so a method
+// called on it applies to the whole value.
+//
+// The invocations below expand to the sum of every value in the range.
+
+use seq::seq;
+
+fn main() {
+    let mut sum = 0;
+    seq!(N in 0..=255 {
+        sum += N;
+    });
+    assert_eq!(sum, 255 * 256 / 2);
+
+    let mut values = Vec::new();
+    seq!(N in -4..4 {
+        values.push(N);
+    });
+    assert_eq!(values, [-4, -3, -2, -1, 0, 1, 2, 3]);
+
+    let mut values = Vec::new();
+    seq!(N in -3..=-1 {
+        values.push(N);
+    });
+    assert_eq!(values, [-3, -2, -1]);
+
+    let mut values = Vec::new();
+    seq!(N in -2i32..0 {
+        values.push((N.abs(), N.pow(2), -N));
+    });
+    assert_eq!(values, [(2, 4, 2), (1, 1, 1)]);
+
+    let mut values = Vec::new();
+    seq!(N in 0i32..2 {
+        values.push(~(N - 2).abs());
+    });
+    assert_eq!(values, [2, 1]);
+}
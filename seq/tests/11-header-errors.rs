@@ -0,0 +1,38 @@
+// Mistakes in the header are reported at the token that is wrong, not at the
+// whole invocation.
+
+use seq::seq;
+
+seq!(N of 0..4 {});
+
+seq!(N in 0...4 {});
+
+seq!(N in 0..x {});
+
+seq!(N in -0x8000_0000_0000_0000_0000_0000_0000_0001..0 {});
+
+seq!(N in 0..4 {} extra);
+
+seq!(N in (0..4).skip(1) {});
+
+seq!(N in (0..4).step_by(0) {});
+
+seq!(N in (0..4 x).rev() {});
+
+seq!(N in 0u8..4u16 {});
+
+seq!(N in 0..(1 << 200) {});
+
+seq!(N in 0..(N_REGS * 2) {});
+
+seq!(N in 0..4, N in 0..4 {});
+
+seq!(#[tracing] N in 0..4 {});
+
+seq!(N in 0.. {});
+
+seq!(N in 0..4);
+
+seq!(N in (0..4).step_by());
+
+fn main() {}
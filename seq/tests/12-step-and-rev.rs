@@ -0,0 +1,40 @@
+// A parenthesized range can be followed by `.step_by(n)` and `.rev()`, which
+// work like the iterator methods of the same name and can be chained. Both the
+// whole body and #(...)* sections repeat for the values in that order. Only
+// the values kept are made, so a wide range with a large step is cheap.
+
+use seq::seq;
+
+seq!(N in (0..64).step_by(16) {
+    #(
+        const REG_OFFSET_#N: usize = N;
+    )*
+});
+
+fn main() {
+    assert_eq!([REG_OFFSET_0, REG_OFFSET_16, REG_OFFSET_32, REG_OFFSET_48], [0, 16, 32, 48]);
+
+    let mut values = Vec::new();
+    seq!(N in (0..8).rev() {
+        values.push(N);
+    });
+    assert_eq!(values, [7, 6, 5, 4, 3, 2, 1, 0]);
+
+    let mut values = Vec::new();
+    seq!(N in (0..=10).step_by(5).rev() {
+        values.push(N);
+    });
+    assert_eq!(values, [10, 5, 0]);
+
+    let mut windows: Vec<u64> = Vec::new();
+    seq!(N in (0..0x1_0000_0000).step_by(0x4000_0000) {
+        windows.push(N);
+    });
+    assert_eq!(windows, [0, 0x4000_0000, 0x8000_0000, 0xc000_0000]);
+
+    let mut values: Vec<i64> = Vec::new();
+    seq!(N in (-0x7fff_ffff_ffff_ffff..=0x7fff_ffff_ffff_ffff).rev().step_by(0x7fff_ffff_ffff_ffff).rev() {
+        values.push(N);
+    });
+    assert_eq!(values, [-i64::MAX, 0, i64::MAX]);
+}
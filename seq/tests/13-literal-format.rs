@@ -0,0 +1,43 @@
+// The literal substituted for the loop variable is written the way the range
+// bounds are: with the same suffix, in the same radix, and padded with zeros to
+// the same number of digits. Values aren't limited to i32, anything that fits
+// in an i128 works, which covers all of u64.
+//
+// Writing a bound with leading zeros, like `00..16`, is also how pasted
+// identifiers get zero-padded: `Reg#N` becomes Reg00, Reg01, ... Reg15.
+
+use seq::seq;
+
+seq!(N in 00..16 {
+    #[derive(Debug)]
+    struct Reg#N;
+});
+
+fn main() {
+    let mut suffixed = Vec::new();
+    seq!(N in 0u8..3u8 {
+        suffixed.push(stringify!(N));
+    });
+    assert_eq!(suffixed, ["0u8", "1u8", "2u8"]);
+
+    let mut hex = Vec::new();
+    seq!(N in 0x00..0x10 {
+        hex.push(stringify!(N));
+    });
+    assert_eq!(hex.first(), Some(&"0x00"));
+    assert_eq!(hex.last(), Some(&"0x0f"));
+
+    let mut wide: Vec<u64> = Vec::new();
+    seq!(N in 0xFFFF_FFFF_FFFF_FFFEu64..=0xFFFF_FFFF_FFFF_FFFFu64 {
+        wide.push(N);
+    });
+    assert_eq!(wide, [u64::MAX - 1, u64::MAX]);
+
+    let mut huge: Vec<i128> = Vec::new();
+    seq!(N in -100000000000000000000000i128..-99999999999999999999998i128 {
+        huge.push(N);
+    });
+    assert_eq!(huge, [-100000000000000000000000, -99999999999999999999999]);
+
+    assert_eq!(format!("{:?} {:?}", Reg00, Reg15), "Reg00 Reg15");
+}
@@ -0,0 +1,36 @@
+// The bounds can be arithmetic on integer literals, like `0..(N_REGS * 2)`
+// where N_REGS comes from a macro_rules! macro, or `1 << 3`. The operators are
+// the ones Rust has for integers, with the same precedence.
+//
+// Fragments a macro_rules! macro passes along, like `$n:literal` or `$n:expr`,
+// arrive wrapped in invisible groups, which the bounds see through.
+
+use seq::seq;
+
+macro_rules! registers {
+    ($n_regs:literal, $base:expr) => {
+        seq!(N in $base..($base + $n_regs * 2) {
+            #(
+                const REG_#N: u32 = N;
+            )*
+        });
+    };
+}
+
+registers!(2, 16);
+
+fn main() {
+    assert_eq!([REG_16, REG_17, REG_18, REG_19], [16, 17, 18, 19]);
+
+    let mut values = Vec::new();
+    seq!(N in 1 << 3..=(16 - 3 * 2) % 7 + 8 {
+        values.push(N);
+    });
+    assert_eq!(values, [8, 9, 10, 11]);
+
+    let mut values = Vec::new();
+    seq!(N in -(2 + 1)..0xF & 2 {
+        values.push(N);
+    });
+    assert_eq!(values, [-3, -2, -1, 0, 1]);
+}
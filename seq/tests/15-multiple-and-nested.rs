@@ -0,0 +1,55 @@
+// Several loop variables separated by commas repeat the body for every
+// combination of their values, the last variable changing fastest. Identifiers
+// can paste in more than one of them, like `cell_#R#_#C` giving cell_0_0,
+// cell_0_1 and so on.
+//
+// A seq! invocation in the body is expanded after the outer one, so it sees
+// the outer variables' values, also in its header. If it binds a variable of
+// the same name as an outer one, that name means the inner variable inside it.
+// Pasting works across them, `cell_#R#C` in the inner one gives cell_00 and so on.
+
+use seq::seq;
+
+seq!(R in 0..2, C in 0..3 {
+    #(
+        const CELL_#R#_#C: (u8, u8) = (R, C);
+    )*
+});
+
+seq!(R in 0..3 {
+    seq!(C in 0..=R {
+        #(
+            const LOWER_#R#_#C: u8 = R * 10 + C;
+        )*
+    });
+});
+
+fn main() {
+    assert_eq!(CELL_0_0, (0, 0));
+    assert_eq!(CELL_0_2, (0, 2));
+    assert_eq!(CELL_1_1, (1, 1));
+
+    assert_eq!([LOWER_0_0, LOWER_1_0, LOWER_1_1, LOWER_2_2], [0, 10, 11, 22]);
+
+    let mut order = Vec::new();
+    seq!(A in 0..2, B in 0..2 {
+        order.push((A, B));
+    });
+    assert_eq!(order, [(0, 0), (0, 1), (1, 0), (1, 1)]);
+
+    let mut shadowed = Vec::new();
+    seq!(N in 0..2 {
+        seq!(N in 10..12 {
+            shadowed.push(N);
+        });
+    });
+    assert_eq!(shadowed, [10, 11, 10, 11]);
+
+    let mut cells = Vec::new();
+    seq!(R in 0..2 {
+        seq!(C in 0..2 {
+            cells.push(stringify!(cell_#R#C));
+        });
+    });
+    assert_eq!(cells, ["cell_00", "cell_01", "cell_10", "cell_11"]);
+}
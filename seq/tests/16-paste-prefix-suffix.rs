@@ -0,0 +1,40 @@
+// Besides `prefix#N`, identifiers can have a suffix pasted after the value as in
+// `Reg#N#Mask`, and lifetimes can be numbered too with `'a#N`. With the
+// variable first, `N#suffix` gives the value as a literal with that suffix,
+// like `0u16` for `N#u16`.
+//
+// If pasting doesn't produce a valid identifier or literal, the error points at
+// the `#` that did it.
+
+use seq::seq;
+
+seq!(N in 0..3 {
+    #[derive(Debug, PartialEq)]
+    pub struct Reg#N#Mask;
+
+    fn wait_#N#_ms() -> u64 {
+        N * 10
+    }
+
+    fn longest#N<'a#N>(a: &'a#N str, b: &'a#N str) -> &'a#N str {
+        if a.len() >= b.len() { a } else { b }
+    }
+});
+
+fn main() {
+    assert_eq!(Reg2Mask, Reg2Mask);
+    assert_eq!(wait_0_ms() + wait_1_ms() + wait_2_ms(), 30);
+    assert_eq!(longest1("ab", "c"), "ab");
+
+    let mut sizes = Vec::new();
+    seq!(N in 1..3 {
+        sizes.push(std::mem::size_of_val(&N#u16));
+    });
+    assert_eq!(sizes, [2, 2]);
+
+    let mut tokens = Vec::new();
+    seq!(N in 0..2 {
+        tokens.push(stringify!(N#_ms));
+    });
+    assert_eq!(tokens, ["0_ms", "1_ms"]);
+}
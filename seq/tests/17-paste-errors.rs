@@ -0,0 +1,20 @@
+// Mistakes in the body that seq! finds while expanding are reported where they
+// are: a negative value can't be pasted into an identifier, and `~(...)` only
+// takes arithmetic on the loop variables and literals. This is a compile_fail
+// test.
+
+use seq::seq;
+
+seq!(N in -1..1 {
+    struct Reg#N;
+});
+
+seq!(N in 0..2 {
+    const X: u32 = ~(N * WIDTH);
+});
+
+seq!(N in 0..2 {
+    const Y: u32 = ~(N << 200);
+});
+
+fn main() {}
@@ -0,0 +1,48 @@
+// `~(...)` in the body is worked out while expanding, with the loop variables'
+// values in it, and replaced by the resulting literal. That's where a literal
+// is needed rather than an expression, like const generic arguments, patterns,
+// or other macros looking at the tokens. The operators are the same as in the
+// bounds.
+//
+// Inside a nested seq!, the arithmetic can use the variables of both.
+
+use seq::seq;
+
+pub struct Bits<const W: usize>;
+
+seq!(N in 1..=4 {
+    pub type Word#N = Bits<~(N * 8)>;
+});
+
+fn width<const W: usize>(_: &Bits<W>) -> usize {
+    W
+}
+
+fn main() {
+    let word: Word4 = Bits;
+    assert_eq!(width(&word), 32);
+
+    let mut set = Vec::new();
+    for mask in [1u8, 4, 8] {
+        seq!(N in 0..4 {
+            if let ~(1 << N) = mask {
+                set.push(N);
+            }
+        });
+    }
+    assert_eq!(set, [0, 2, 3]);
+
+    let mut tokens = Vec::new();
+    seq!(N in 0u8..2u8 {
+        tokens.push(stringify!(~(N * 4 + 1)));
+    });
+    assert_eq!(tokens, ["1u8", "5u8"]);
+
+    let mut cells = Vec::new();
+    seq!(R in 0..2 {
+        seq!(C in 0..3 {
+            cells.push(stringify!(~(R * 3 + C)));
+        });
+    });
+    assert_eq!(cells, ["0", "1", "2", "3", "4", "5"]);
+}
@@ -0,0 +1,45 @@
+// A body can have any number of `#(...)*` sections, inside any group; each
+// one is repeated in place and the rest of the body is written out once, with
+// its brackets, braces and parentheses where they were.
+//
+// A token between the `)` and the `*`, like `#(...),*`, is put between the
+// copies, so lists don't need a trailing separator.
+
+use seq::seq;
+
+seq!(N in 0..4 {
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    enum Channel {
+        #(Ch#N,)*
+    }
+
+    impl Channel {
+        const ALL: [Channel; 4] = [#(Channel::Ch#N),*];
+
+        fn index(self) -> usize {
+            match self {
+                #(Channel::Ch#N => N,)*
+            }
+        }
+    }
+
+    fn sum() -> usize {
+        0 #(+ N)*
+    }
+
+    fn table() -> [[usize; 2]; 4] {
+        [#([N, N * N]),*]
+    }
+});
+
+fn main() {
+    assert_eq!(Channel::ALL, [Channel::Ch0, Channel::Ch1, Channel::Ch2, Channel::Ch3]);
+    assert_eq!(Channel::Ch2.index(), 2);
+    assert_eq!(sum(), 6);
+    assert_eq!(table()[3], [3, 9]);
+
+    let flags: u8 = seq!(N in 0..3 {
+        #(1 << N)|*
+    });
+    assert_eq!(flags, 0b111);
+}
@@ -0,0 +1,40 @@
+// `#[trace]` before the loop variables writes the expansion, with a line break
+// after each `;` and around the contents of braces, to a file under
+// `seq-trace/` in OUT_DIR, or in the target directory for a crate without a
+// build script. SEQ_TRACE_DIR overrides the directory, as it does here. The
+// file is named after the loop variables, like `N-<hash>.rs`, and the
+// expansion itself is unchanged.
+
+use seq::seq;
+
+seq!(#[trace] N in 0..4 {
+    #[derive(Clone, Copy)]
+    enum Irq {
+        #(Irq#N = ~(1 << N),)*
+    }
+
+    fn is_set(mask: &u8, irq: Irq) -> bool {
+        *mask & irq as u8 != 0
+    }
+});
+
+const EXPECTED: &str = "\
+#[derive(Clone, Copy)] enum Irq {
+    Irq0 = 1, Irq1 = 2, Irq2 = 4, Irq3 = 8,
+}
+fn is_set(mask : & u8, irq : Irq) -> bool {
+    * mask & irq as u8 != 0
+}
+";
+
+fn main() {
+    assert_eq!(Irq::Irq3 as u8, 8);
+    assert!(is_set(&0b1000, Irq::Irq3));
+
+    let traces = std::fs::read_dir(env!("SEQ_TRACE_DIR")).unwrap();
+    let written = traces.map(|entry| entry.unwrap().path()).any(|path| {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        name.starts_with("N-") && std::fs::read_to_string(&path).unwrap() == EXPECTED
+    });
+    assert!(written);
+}
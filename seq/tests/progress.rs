@@ -1,13 +1,27 @@
 #[test]
 fn tests() {
+    // where tests/20-trace.rs has its trace written, rather than the target directory of the
+    // project trybuild generates
+    std::env::set_var("SEQ_TRACE_DIR", concat!(env!("CARGO_TARGET_TMPDIR"), "/seq-trace"));
     let t = trybuild::TestCases::new();
     t.pass("tests/01-parse-header.rs");
     t.pass("tests/02-parse-body.rs");
     t.compile_fail("tests/03-expand-four-errors.rs");
     t.pass("tests/04-paste-ident.rs");
-    //t.pass("tests/05-repeat-section.rs");
-    //t.pass("tests/06-init-array.rs");
-    //t.pass("tests/07-inclusive-range.rs");
-    //t.compile_fail("tests/08-ident-span.rs");
-    //t.pass("tests/09-interaction-with-macrorules.rs");
+    t.pass("tests/05-repeat-section.rs");
+    t.pass("tests/06-init-array.rs");
+    t.pass("tests/07-inclusive-range.rs");
+    t.compile_fail("tests/08-ident-span.rs");
+    t.pass("tests/09-interaction-with-macrorules.rs");
+    t.pass("tests/10-inclusive-negative-range.rs");
+    t.compile_fail("tests/11-header-errors.rs");
+    t.pass("tests/12-step-and-rev.rs");
+    t.pass("tests/13-literal-format.rs");
+    t.pass("tests/14-const-bounds.rs");
+    t.pass("tests/15-multiple-and-nested.rs");
+    t.pass("tests/16-paste-prefix-suffix.rs");
+    t.compile_fail("tests/17-paste-errors.rs");
+    t.pass("tests/18-interpolated-arithmetic.rs");
+    t.pass("tests/19-repeat-sections.rs");
+    t.pass("tests/20-trace.rs");
 }